@@ -0,0 +1,62 @@
+//! Encoding and decoding of frames, and the handshake of a connection.
+//!
+//! Run them with `cargo bench`, giving a name like `cargo bench -- decode` to only run the
+//! benchmarks containing it. Criterion compares each run to the previous one, and
+//! `--save-baseline <name>` then `--baseline <name>` compare to a named one.
+use bytes::{Bytes, BytesMut};
+use criterion::{criterion_group, criterion_main, BenchmarkId, Criterion, Throughput};
+use std::hint::black_box;
+use zmtp::packets::{null, RawFrame};
+use zmtp::sockets::{Dealer, Rep};
+
+const SIZES: [usize; 3] = [16, 1024, 64 * 1024];
+
+fn encode(c: &mut Criterion) {
+    let mut group = c.benchmark_group("encode");
+    for size in SIZES {
+        let frame = null::Frame::Message(Bytes::from(vec![0; size]));
+        group.throughput(Throughput::Bytes(size as u64));
+        group.bench_with_input(BenchmarkId::from_parameter(size), &frame, |b, frame| {
+            b.iter(|| black_box(frame).to_vec_u8());
+        });
+    }
+    group.finish();
+}
+
+/// Like the receiving half of a connection, decode the frames of a buffer of messages.
+fn decode(c: &mut Criterion) {
+    let frames = 64;
+    let mut group = c.benchmark_group("decode");
+    for size in SIZES {
+        let frame = null::Frame::Message(Bytes::from(vec![0; size])).to_vec_u8();
+        let received = BytesMut::from(&frame.repeat(frames)[..]);
+        group.throughput(Throughput::Bytes(received.len() as u64));
+        let id = BenchmarkId::from_parameter(format!("{size}x{frames}"));
+        group.bench_with_input(id, &received, |b, received| {
+            b.iter(|| {
+                let mut buf = received.clone();
+                while let Some(raw) = RawFrame::decode(&mut buf).unwrap() {
+                    black_box(null::Frame::try_from(raw).unwrap());
+                }
+            });
+        });
+    }
+    group.finish();
+}
+
+fn handshake(c: &mut Criterion) {
+    let runtime = tokio::runtime::Runtime::new().unwrap();
+    let rep = Rep::new();
+    let addr = runtime.block_on(rep.bind("127.0.0.1", 0)).unwrap();
+    c.bench_function("handshake", |b| {
+        b.iter(|| {
+            runtime.block_on(async {
+                let dealer = Dealer::new();
+                dealer.connect("127.0.0.1", addr.port()).await.unwrap();
+            });
+        });
+    });
+}
+
+criterion_group!(benches, encode, decode, handshake);
+criterion_main!(benches);
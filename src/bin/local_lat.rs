@@ -0,0 +1,29 @@
+//! Echo the messages of `remote_lat`, like the libzmq `local_lat`.
+use futures::StreamExt;
+use tokio::main;
+use zmtp::sockets::{EventKind, Rep};
+
+#[path = "perf/mod.rs"]
+mod perf;
+
+#[main]
+async fn main() -> Result<(), Box<dyn std::error::Error>> {
+    let args = perf::Args::parse("bind-to", "roundtrip-count");
+    let mut rep = Rep::new();
+    let mut events = rep.monitor().events();
+    rep.bind(&args.host, args.port).await?;
+    for _ in 0..args.count {
+        let msg = rep.recv().await?;
+        if msg.0.iter().map(|part| part.len()).sum::<usize>() != args.size {
+            return Err("message of incorrect size received".into());
+        }
+        rep.send(msg).await?;
+    }
+    // remote_lat disconnects once it received every reply
+    while let Some(event) = events.next().await {
+        if let EventKind::Disconnected(_) = event.kind {
+            break;
+        }
+    }
+    Ok(())
+}
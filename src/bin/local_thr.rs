@@ -0,0 +1,31 @@
+//! Measure the throughput of the messages of `remote_thr`, like the libzmq `local_thr`.
+use std::time::Instant;
+use tokio::main;
+use zmtp::sockets::Pull;
+
+#[path = "perf/mod.rs"]
+mod perf;
+
+#[main]
+async fn main() -> Result<(), Box<dyn std::error::Error>> {
+    let args = perf::Args::parse("bind-to", "message-count");
+    let pull = Pull::new();
+    pull.bind(&args.host, args.port).await?;
+    // the timer starts with the first message, like libzmq
+    pull.recv().await?;
+    let start = Instant::now();
+    for _ in 1..args.count {
+        let msg = pull.recv().await?;
+        if msg.0.iter().map(|part| part.len()).sum::<usize>() != args.size {
+            return Err("message of incorrect size received".into());
+        }
+    }
+    let elapsed = start.elapsed().as_secs_f64().max(f64::EPSILON);
+    let throughput = args.count as f64 / elapsed;
+    let megabits = throughput * args.size as f64 * 8.0 / 1e6;
+    println!("message size: {} [B]", args.size);
+    println!("message count: {}", args.count);
+    println!("mean throughput: {} [msg/s]", throughput as u64);
+    println!("mean throughput: {megabits:.3} [Mb/s]");
+    Ok(())
+}
@@ -0,0 +1,45 @@
+//! The command line shared by the performance tools, like the libzmq ones.
+use std::process::exit;
+
+/// The arguments of a performance tool: the endpoint, the message size and the message count.
+pub struct Args {
+    pub host: String,
+    pub port: u16,
+    pub size: usize,
+    pub count: usize,
+}
+
+impl Args {
+    /// Parse the arguments, or print the usage and exit.
+    ///
+    /// `endpoint` and `count` name the arguments in the usage, like `bind-to` and
+    /// `roundtrip-count`.
+    pub fn parse(endpoint: &str, count: &str) -> Self {
+        let args: Vec<_> = std::env::args().collect();
+        let parsed = match &args[..] {
+            [_, address, size, count] => Self::from_strs(address, size, count),
+            _ => None,
+        };
+        parsed.unwrap_or_else(|| {
+            let name = args
+                .first()
+                .map_or("perf", |name| name.rsplit('/').next().unwrap());
+            eprintln!("usage: {name} <{endpoint}> <message-size> <{count}>");
+            exit(1)
+        })
+    }
+
+    fn from_strs(address: &str, size: &str, count: &str) -> Option<Self> {
+        let (host, port) = address.strip_prefix("tcp://")?.rsplit_once(':')?;
+        let host = match host {
+            "*" => "0.0.0.0",
+            host => host.trim_start_matches('[').trim_end_matches(']'),
+        };
+        Some(Self {
+            host: host.to_string(),
+            port: port.parse().ok()?,
+            size: size.parse().ok()?,
+            count: count.parse().ok()?,
+        })
+    }
+}
@@ -0,0 +1,20 @@
+//! The records read by the command line tools, split into the parts of messages.
+use bytes::{Buf, Bytes};
+use zmtp::Multipart;
+
+/// Split a record into the parts of a message on `separator`.
+pub fn parts(mut record: Bytes, separator: &[u8]) -> Multipart {
+    if separator.is_empty() {
+        return record.into();
+    }
+    let mut parts = Vec::new();
+    while let Some(at) = record
+        .windows(separator.len())
+        .position(|window| window == separator)
+    {
+        parts.push(record.split_to(at));
+        record.advance(separator.len());
+    }
+    parts.push(record);
+    parts.into()
+}
@@ -0,0 +1,30 @@
+//! Measure the round trip latency with `local_lat`, like the libzmq `remote_lat`.
+use bytes::Bytes;
+use futures::{SinkExt, StreamExt};
+use std::time::Instant;
+use tokio::main;
+use zmtp::sockets::Zmtp;
+
+#[path = "perf/mod.rs"]
+mod perf;
+
+#[main]
+async fn main() -> Result<(), Box<dyn std::error::Error>> {
+    let args = perf::Args::parse("connect-to", "roundtrip-count");
+    let mut req = Zmtp::connect(&args.host, args.port).await?;
+    let msg = Bytes::from(vec![0; args.size]);
+    let start = Instant::now();
+    for _ in 0..args.count {
+        req.send(msg.clone().into()).await?;
+        let reply = req.next().await.ok_or("connection closed")??;
+        if reply.0.iter().map(|part| part.len()).sum::<usize>() != args.size {
+            return Err("message of incorrect size received".into());
+        }
+    }
+    let elapsed = start.elapsed();
+    let latency = elapsed.as_secs_f64() * 1e6 / (args.count as f64 * 2.0);
+    println!("message size: {} [B]", args.size);
+    println!("roundtrip count: {}", args.count);
+    println!("average latency: {latency:.3} [us]");
+    Ok(())
+}
@@ -0,0 +1,27 @@
+//! Send messages to `local_thr` as fast as possible, like the libzmq `remote_thr`.
+use bytes::Bytes;
+use futures::StreamExt;
+use tokio::main;
+use zmtp::sockets::{EventKind, Push};
+
+#[path = "perf/mod.rs"]
+mod perf;
+
+#[main]
+async fn main() -> Result<(), Box<dyn std::error::Error>> {
+    let args = perf::Args::parse("connect-to", "message-count");
+    let push = Push::new();
+    let mut events = push.monitor().events();
+    push.connect(&args.host, args.port).await?;
+    let msg = Bytes::from(vec![0; args.size]);
+    for _ in 0..args.count {
+        push.send(msg.clone()).await?;
+    }
+    // local_thr disconnects once it received every message
+    while let Some(event) = events.next().await {
+        if let EventKind::Disconnected(_) = event.kind {
+            break;
+        }
+    }
+    Ok(())
+}
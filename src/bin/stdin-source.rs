@@ -1,21 +1,106 @@
-use futures::TryFutureExt;
-use serde_json::{from_slice, to_vec};
+use bytes::{Buf, Bytes, BytesMut};
+use futures::{SinkExt, Stream, StreamExt};
+use serde::{Deserialize, Serialize};
 use tokio::main;
+use tokio_util::codec::{AnyDelimiterCodec, Decoder, FramedRead, LengthDelimitedCodec};
 use twelf::{config, Layer};
 use zmtp::sockets;
 
+#[path = "records/mod.rs"]
+mod records;
+
+/// How the records are delimited on stdin.
+#[derive(Debug, Clone, Copy, Default, Deserialize, Serialize)]
+#[serde(rename_all = "kebab-case")]
+enum Framing {
+    /// One record per line.
+    #[default]
+    Newline,
+    /// Records terminated by a NUL byte.
+    Nul,
+    /// Records preceded by their size, as a 4 bytes big-endian integer.
+    LengthPrefixed,
+    /// One record per JSON value, rejecting invalid JSON.
+    Json,
+}
+
 #[config]
 struct Conf {
     /// The IP address of the stdout-sink node to contact
     host: String,
     /// The port of the stdout-sink node to contact
     port: u16,
+    /// How records are delimited on stdin: newline, nul, length-prefixed or json
+    #[serde(default)]
+    framing: Framing,
+    /// Split each record into the frames of a multipart message on this separator
+    #[serde(default)]
+    separator: String,
+}
+
+/// Decode a stream of JSON values, each one sent as it was written.
+struct JsonCodec;
+
+impl JsonCodec {
+    fn value(buf: &mut BytesMut, eof: bool) -> std::io::Result<Option<Bytes>> {
+        let start = buf.len() - buf.trim_ascii_start().len();
+        let mut values = serde_json::Deserializer::from_slice(&buf[start..])
+            .into_iter::<serde::de::IgnoredAny>();
+        match values.next() {
+            // a number ending the buffer may go on in the next read
+            Some(Ok(_)) if values.byte_offset() == buf.len() - start && !eof => Ok(None),
+            Some(Ok(_)) => {
+                let len = values.byte_offset();
+                buf.advance(start);
+                Ok(Some(buf.split_to(len).freeze()))
+            }
+            Some(Err(err)) if err.is_eof() && !eof => Ok(None),
+            Some(Err(err)) => Err(err.into()),
+            None => {
+                buf.clear();
+                Ok(None)
+            }
+        }
+    }
+}
+
+impl Decoder for JsonCodec {
+    type Item = Bytes;
+    type Error = std::io::Error;
+
+    fn decode(&mut self, buf: &mut BytesMut) -> std::io::Result<Option<Bytes>> {
+        Self::value(buf, false)
+    }
+
+    fn decode_eof(&mut self, buf: &mut BytesMut) -> std::io::Result<Option<Bytes>> {
+        Self::value(buf, true)
+    }
+}
+
+/// The records read from stdin.
+fn records(framing: Framing) -> Box<dyn Stream<Item = std::io::Result<Bytes>> + Unpin> {
+    let stdin = tokio::io::stdin;
+    let delimited = |delimiter: &[u8]| {
+        let codec = AnyDelimiterCodec::new(delimiter.to_vec(), Vec::new());
+        FramedRead::new(stdin(), codec).map(|record| {
+            record.map_err(|err| std::io::Error::new(std::io::ErrorKind::InvalidData, err))
+        })
+    };
+    match framing {
+        Framing::Newline => Box::new(delimited(b"\n")),
+        Framing::Nul => Box::new(delimited(b"\0")),
+        Framing::LengthPrefixed => Box::new(
+            FramedRead::new(stdin(), LengthDelimitedCodec::new())
+                .map(|record| record.map(BytesMut::freeze)),
+        ),
+        Framing::Json => Box::new(FramedRead::new(stdin(), JsonCodec)),
+    }
 }
 
 #[main]
 async fn main() -> Result<(), Box<dyn std::error::Error>> {
     // Will generate global arguments for each of your fields inside your configuration struct
-    let app = clap::Command::new("zmtp").args(&Conf::clap_args());
+    let app = clap::Command::new("zmtp").args(Conf::clap_args());
 
     // Init configuration with layers, each layers override only existing fields
     let config = Conf::with_layers(&[
@@ -23,16 +108,21 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
         Layer::Env(Some("IPC_CHAN_".to_string())),
         Layer::Clap(app.get_matches()),
     ])?;
-    println!("Connecting to tcp://{}:{}...", config.host, config.port);
+    eprintln!("Connecting to tcp://{}:{}...", config.host, config.port);
     let mut s = sockets::Zmtp::connect(config.host.as_str(), config.port).await?;
-    println!("{:?}", s.version());
-    let msg = to_vec(&String::from("Hi!")).unwrap();
-    s.send_frame(msg.into())
-        .map_ok(|msg| {
-            if let zmtp::packets::null::Frame::Message(msg) = msg {
-                println!("REP: {}", from_slice::<String>(&msg).unwrap())
-            }
-        })
-        .err_into()
-        .await
+    eprintln!("{:?}", s.version());
+    let separator = config.separator.as_bytes();
+    let mut records = records(config.framing);
+    while let Some(record) = records.next().await {
+        s.send(records::parts(record?, separator)).await?;
+        // a REQ socket waits for the reply before sending the next request
+        let reply = match s.next().await {
+            Some(reply) => reply?,
+            None => return Err("connection closed before the reply".into()),
+        };
+        let parts: Vec<_> = reply.0.iter().map(|p| String::from_utf8_lossy(p)).collect();
+        println!("REP: {}", parts.join(&config.separator));
+    }
+    s.close().await?;
+    Ok(())
 }
@@ -0,0 +1,154 @@
+use bytes::Bytes;
+use serde::{Deserialize, Serialize};
+use std::io::Write;
+use tokio::main;
+use twelf::{config, Layer};
+use zmtp::sockets::{Pull, Rep, Router, SocketType, Sub};
+use zmtp::Multipart;
+
+/// How the received messages are printed on stdout, one per line.
+#[derive(Debug, Clone, Copy, Default, Deserialize, Serialize)]
+#[serde(rename_all = "kebab-case")]
+enum Format {
+    /// The bytes of the frames, as they were received.
+    #[default]
+    Raw,
+    /// The bytes of the frames in hexadecimal.
+    Hex,
+    /// A JSON array of the frames, invalid UTF-8 being replaced.
+    Json,
+    /// The frames with their non-printable bytes escaped.
+    Escaped,
+}
+
+fn rep() -> SocketType {
+    SocketType::Rep
+}
+
+#[config]
+struct Conf {
+    /// The IP address to accept the stdin-source nodes on
+    host: String,
+    /// The port to accept the stdin-source nodes on
+    port: u16,
+    /// The type of the bound socket: REP, PULL, SUB or ROUTER
+    #[serde(default = "rep")]
+    socket_type: SocketType,
+    /// How messages are printed: raw, hex, json or escaped
+    #[serde(default)]
+    format: Format,
+    /// Print the frames of a multipart message joined by this separator
+    #[serde(default)]
+    separator: String,
+    /// The topic a SUB socket subscribes to, every message by default
+    #[serde(default)]
+    subscribe: String,
+    /// Reply this to each message, REP sockets echo the request back by default
+    reply: Option<String>,
+}
+
+/// The socket types stdout-sink can bind.
+enum Socket {
+    Rep(Rep),
+    Pull(Pull),
+    Sub(Sub),
+    Router(Router),
+}
+
+impl Socket {
+    fn new(conf: &Conf) -> Result<Self, Box<dyn std::error::Error>> {
+        Ok(match conf.socket_type {
+            SocketType::Rep => Self::Rep(Rep::new()),
+            SocketType::Pull => Self::Pull(Pull::new()),
+            SocketType::Sub => {
+                let sub = Sub::new();
+                sub.subscribe(&conf.subscribe);
+                Self::Sub(sub)
+            }
+            SocketType::Router => Self::Router(Router::new()),
+            other => return Err(format!("stdout-sink can't bind {other} sockets").into()),
+        })
+    }
+
+    async fn bind(&self, host: &str, port: u16) -> zmtp::Result<std::net::SocketAddr> {
+        match self {
+            Self::Rep(s) => s.bind(host, port).await,
+            Self::Pull(s) => s.bind(host, port).await,
+            Self::Sub(s) => s.bind(host, port).await,
+            Self::Router(s) => s.bind(host, port).await,
+        }
+    }
+
+    async fn recv(&mut self) -> zmtp::Result<Multipart> {
+        match self {
+            Self::Rep(s) => s.recv().await,
+            Self::Pull(s) => s.recv().await,
+            Self::Sub(s) => s.recv().await,
+            Self::Router(s) => s.recv().await,
+        }
+    }
+
+    /// Answer a received message, if the socket type can.
+    async fn reply(&mut self, msg: Multipart, reply: Option<&str>) -> zmtp::Result<()> {
+        match (self, reply) {
+            (Self::Rep(s), None) => s.send(msg).await,
+            (Self::Rep(s), Some(reply)) => s.send(Bytes::copy_from_slice(reply.as_bytes())).await,
+            (Self::Router(s), Some(reply)) => {
+                // keep the routing id, and the envelope of REQ peers
+                let mut parts = msg.0;
+                let envelope = parts.iter().position(|part| part.is_empty()).unwrap_or(0);
+                parts.truncate(envelope + 1);
+                parts.push(Bytes::copy_from_slice(reply.as_bytes()));
+                s.send(Multipart(parts)).await
+            }
+            _ => Ok(()),
+        }
+    }
+}
+
+/// Print a message on a single line.
+fn print(out: &mut impl Write, msg: &Multipart, conf: &Conf) -> std::io::Result<()> {
+    let separator = conf.separator.as_bytes();
+    match conf.format {
+        Format::Json => {
+            let parts: Vec<_> = msg.0.iter().map(|p| String::from_utf8_lossy(p)).collect();
+            serde_json::to_writer(&mut *out, &parts)?;
+        }
+        format => {
+            for (i, part) in msg.0.iter().enumerate() {
+                if i > 0 {
+                    out.write_all(separator)?;
+                }
+                match format {
+                    Format::Hex => part.iter().try_for_each(|b| write!(out, "{b:02x}"))?,
+                    Format::Escaped => write!(out, "{}", part.escape_ascii())?,
+                    _ => out.write_all(part)?,
+                }
+            }
+        }
+    }
+    out.write_all(b"\n")?;
+    out.flush()
+}
+
+#[main]
+async fn main() -> Result<(), Box<dyn std::error::Error>> {
+    // Will generate global arguments for each of your fields inside your configuration struct
+    let app = clap::Command::new("zmtp").args(Conf::clap_args());
+
+    // Init configuration with layers, each layers override only existing fields
+    let config = Conf::with_layers(&[
+        Layer::Toml("ipc-chan.toml".into()),
+        Layer::Env(Some("IPC_CHAN_".to_string())),
+        Layer::Clap(app.get_matches()),
+    ])?;
+    let mut socket = Socket::new(&config)?;
+    let addr = socket.bind(config.host.as_str(), config.port).await?;
+    eprintln!("{} socket bound on tcp://{addr}", config.socket_type);
+    let mut stdout = std::io::stdout();
+    loop {
+        let msg = socket.recv().await?;
+        print(&mut stdout, &msg, &config)?;
+        socket.reply(msg, config.reply.as_deref()).await?;
+    }
+}
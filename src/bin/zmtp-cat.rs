@@ -0,0 +1,407 @@
+use bytes::Bytes;
+use clap::Parser;
+use futures::stream::{self, LocalBoxStream};
+use futures::{StreamExt, TryStreamExt};
+use std::path::PathBuf;
+use std::time::Duration;
+use tokio::main;
+use tokio_util::codec::{FramedRead, LinesCodec};
+use zmtp::sockets::{
+    Channel, Client, Dealer, Dish, Gather, Options, Peer, Pub, Pull, Push, Radio, Rep, Router,
+    RoutingId, Scatter, Server, SocketType, Sub, XPub, XSub, Zmtp,
+};
+use zmtp::Multipart;
+
+#[path = "records/mod.rs"]
+mod records;
+
+type Error = Box<dyn std::error::Error>;
+
+/// Connect or bind a ZMQ socket, send messages and print the received ones.
+///
+/// Messages of ROUTER, SERVER and PEER sockets start with the routing id of their peer, and
+/// messages of RADIO and DISH sockets with their group.
+#[derive(Debug, Parser)]
+#[command(name = "zmtp-cat")]
+struct Args {
+    /// The socket type, like REQ, SUB or ROUTER
+    #[arg(value_parser = socket_type)]
+    socket_type: SocketType,
+    /// The endpoint, as tcp://host:port
+    #[arg(value_parser = endpoint)]
+    endpoint: (String, u16),
+    /// Bind the endpoint instead of connecting to it
+    #[arg(short, long)]
+    bind: bool,
+    /// Subscribe a SUB or XSUB socket to this topic, or join a DISH socket to this group
+    #[arg(short, long)]
+    subscribe: Vec<String>,
+    /// Send this message instead of the lines of stdin
+    #[arg(short, long)]
+    message: Vec<String>,
+    /// Send the content of this file instead of the lines of stdin
+    #[arg(short, long)]
+    file: Vec<PathBuf>,
+    /// Split the sent messages into frames, and join the printed frames, on this separator
+    #[arg(long)]
+    separator: Option<String>,
+    /// Exit after receiving this many messages
+    #[arg(short = 'n', long)]
+    count: Option<usize>,
+    /// Milliseconds left to send the last messages before exiting
+    #[arg(long, default_value_t = 100)]
+    linger: u64,
+    /// The security mechanism, only null is supported
+    #[arg(long, default_value = "null", value_parser = mechanism)]
+    mechanism: Mechanism,
+}
+
+/// The security mechanisms of ZMTP.
+#[derive(Debug, Clone, Copy)]
+enum Mechanism {
+    Null,
+}
+
+fn mechanism(name: &str) -> Result<Mechanism, String> {
+    match name.to_lowercase().as_str() {
+        "null" => Ok(Mechanism::Null),
+        "plain" | "curve" => Err(format!(
+            "the {} mechanism is not supported, only null is",
+            name.to_uppercase()
+        )),
+        _ => Err(format!("unknown mechanism {name}")),
+    }
+}
+
+fn socket_type(name: &str) -> Result<SocketType, String> {
+    SocketType::from_name(name.to_uppercase().as_bytes())
+        .ok_or_else(|| format!("unknown socket type {name}"))
+}
+
+fn endpoint(endpoint: &str) -> Result<(String, u16), String> {
+    let address = endpoint
+        .strip_prefix("tcp://")
+        .ok_or("only tcp:// endpoints are supported")?;
+    let (host, port) = address.rsplit_once(':').ok_or("missing port")?;
+    let port = port.parse().map_err(|_| format!("invalid port {port}"))?;
+    let host = match host {
+        "*" => "0.0.0.0",
+        host => host.trim_start_matches('[').trim_end_matches(']'),
+    };
+    Ok((host.to_string(), port))
+}
+
+impl Args {
+    /// The options of the socket, lingering for `--linger` when closed.
+    fn options(&self) -> Options {
+        // the sockets only handshake with the NULL mechanism
+        let Mechanism::Null = self.mechanism;
+        Options::default().with_linger(Duration::from_millis(self.linger))
+    }
+
+    /// The messages to send, from the arguments, the files or else stdin.
+    fn outgoing(&self) -> LocalBoxStream<'static, Result<Multipart, Error>> {
+        let separator = self.separator.clone().unwrap_or_default();
+        let split = move |record: Bytes| records::parts(record, separator.as_bytes());
+        if self.message.is_empty() && self.file.is_empty() {
+            let lines = FramedRead::new(tokio::io::stdin(), LinesCodec::new());
+            return lines
+                .map_ok(Bytes::from)
+                .err_into()
+                .map_ok(split)
+                .boxed_local();
+        }
+        let messages = stream::iter(self.message.clone()).map(|m| Ok(Bytes::from(m)));
+        let files = stream::iter(self.file.clone())
+            .then(tokio::fs::read)
+            .map_ok(Bytes::from)
+            .err_into();
+        messages.chain(files).map_ok(split).boxed_local()
+    }
+
+    /// Print a received message on a single line, its frames escaped.
+    fn print(&self, msg: &Multipart) {
+        let parts: Vec<_> = msg.0.iter().map(|p| p.escape_ascii().to_string()).collect();
+        println!("{}", parts.join(self.separator.as_deref().unwrap_or(" ")));
+    }
+}
+
+/// Split the routing id or the group off the first frame of a message.
+fn addressed(socket_type: SocketType, msg: Multipart) -> Result<(String, Bytes), Error> {
+    match <[Bytes; 2]>::try_from(msg.0) {
+        Ok([to, body]) => Ok((String::from_utf8(to.to_vec())?, body)),
+        Err(_) => {
+            Err(format!("{socket_type} messages have two frames: the peer, then the body").into())
+        }
+    }
+}
+
+fn routing_id(id: &str) -> Result<RoutingId, Error> {
+    id.parse()
+        .map_err(|_| format!("invalid routing id {id}").into())
+}
+
+/// The sockets sending and receiving independently.
+enum Socket {
+    Dealer(Dealer),
+    Router(Router),
+    Push(Push),
+    Pull(Pull),
+    Pub(Pub),
+    XPub(XPub),
+    Sub(Sub),
+    XSub(XSub),
+    Client(Client),
+    Server(Server),
+    Radio(Radio),
+    Dish(Dish),
+    Scatter(Scatter),
+    Gather(Gather),
+    Channel(Channel),
+    Peer(Peer),
+}
+
+impl Socket {
+    fn new(args: &Args) -> Result<Self, Error> {
+        let options = args.options();
+        Ok(match args.socket_type {
+            SocketType::Dealer => Self::Dealer(Dealer::with_options(options)),
+            SocketType::Router => Self::Router(Router::with_options(options)),
+            SocketType::Push => Self::Push(Push::with_options(options)),
+            SocketType::Pull => Self::Pull(Pull::with_options(options)),
+            SocketType::Pub => Self::Pub(Pub::with_options(options)),
+            SocketType::XPub => Self::XPub(XPub::with_options(options)),
+            SocketType::Sub => {
+                let sub = Sub::with_options(options);
+                args.subscribe.iter().for_each(|topic| sub.subscribe(topic));
+                Self::Sub(sub)
+            }
+            SocketType::XSub => {
+                let xsub = XSub::with_options(options);
+                for topic in &args.subscribe {
+                    xsub.send(Bytes::from([b"\x01", topic.as_bytes()].concat()));
+                }
+                Self::XSub(xsub)
+            }
+            SocketType::Client => Self::Client(Client::with_options(options)),
+            SocketType::Server => Self::Server(Server::with_options(options)),
+            SocketType::Radio => Self::Radio(Radio::with_options(options)),
+            SocketType::Dish => {
+                let dish = Dish::with_options(options);
+                for group in &args.subscribe {
+                    dish.join(group)?;
+                }
+                Self::Dish(dish)
+            }
+            SocketType::Scatter => Self::Scatter(Scatter::with_options(options)),
+            SocketType::Gather => Self::Gather(Gather::with_options(options)),
+            SocketType::Channel => Self::Channel(Channel::with_options(options)),
+            SocketType::Peer => Self::Peer(Peer::with_options(options)),
+            other => return Err(format!("{other} sockets are not supported yet").into()),
+        })
+    }
+
+    async fn open(&self, (host, port): &(String, u16), bind: bool) -> zmtp::Result<()> {
+        macro_rules! open {
+            ($s:expr) => {
+                if bind {
+                    $s.bind(host, *port).await.map(drop)
+                } else {
+                    $s.connect(host, *port).await.map(drop)
+                }
+            };
+        }
+        match self {
+            Self::Dealer(s) => open!(s),
+            Self::Router(s) => open!(s),
+            Self::Push(s) => open!(s),
+            Self::Pull(s) => open!(s),
+            Self::Pub(s) => open!(s),
+            Self::XPub(s) => open!(s),
+            Self::Sub(s) => open!(s),
+            Self::XSub(s) => open!(s),
+            Self::Client(s) => open!(s),
+            Self::Server(s) => open!(s),
+            Self::Radio(s) => open!(s),
+            Self::Dish(s) => open!(s),
+            Self::Scatter(s) => open!(s),
+            Self::Gather(s) => open!(s),
+            Self::Channel(s) => open!(s),
+            Self::Peer(s) => open!(s),
+        }
+    }
+
+    /// Close the socket, returning the number of messages which couldn't be sent in time.
+    async fn close(self) -> usize {
+        match self {
+            Self::Dealer(s) => s.close().await,
+            Self::Router(s) => s.close().await,
+            Self::Push(s) => s.close().await,
+            Self::Pull(s) => s.close().await,
+            Self::Pub(s) => s.close().await,
+            Self::XPub(s) => s.close().await,
+            Self::Sub(s) => s.close().await,
+            Self::XSub(s) => s.close().await,
+            Self::Client(s) => s.close().await,
+            Self::Server(s) => s.close().await,
+            Self::Radio(s) => s.close().await,
+            Self::Dish(s) => s.close().await,
+            Self::Scatter(s) => s.close().await,
+            Self::Gather(s) => s.close().await,
+            Self::Channel(s) => s.close().await,
+            Self::Peer(s) => s.close().await,
+        }
+    }
+
+    fn can_send(&self) -> bool {
+        !matches!(
+            self,
+            Self::Pull(_) | Self::Sub(_) | Self::Dish(_) | Self::Gather(_)
+        )
+    }
+
+    fn can_recv(&self) -> bool {
+        !matches!(
+            self,
+            Self::Push(_) | Self::Pub(_) | Self::Radio(_) | Self::Scatter(_)
+        )
+    }
+
+    async fn send(&self, msg: Multipart) -> Result<(), Error> {
+        match self {
+            Self::Dealer(s) => s.send(msg).await?,
+            Self::Router(s) => s.send(msg).await?,
+            Self::Push(s) => s.send(msg).await?,
+            Self::Pub(s) => s.send(msg),
+            Self::XPub(s) => s.send(msg),
+            Self::XSub(s) => s.send(msg),
+            Self::Client(s) => s.send(msg).await?,
+            Self::Scatter(s) => s.send(msg).await?,
+            Self::Channel(s) => s.send(msg).await?,
+            Self::Server(s) => {
+                let (to, body) = addressed(SocketType::Server, msg)?;
+                s.send(routing_id(&to)?, body).await?
+            }
+            Self::Peer(s) => {
+                let (to, body) = addressed(SocketType::Peer, msg)?;
+                s.send(routing_id(&to)?, body).await?
+            }
+            Self::Radio(s) => {
+                let (group, body) = addressed(SocketType::Radio, msg)?;
+                s.send(&group, body).await?
+            }
+            Self::Pull(_) | Self::Sub(_) | Self::Dish(_) | Self::Gather(_) => {}
+        }
+        Ok(())
+    }
+
+    async fn recv(&self) -> zmtp::Result<Multipart> {
+        let from = |from: String, body| Multipart(vec![from.into(), body]);
+        Ok(match self {
+            Self::Dealer(s) => s.recv().await?,
+            Self::Router(s) => s.recv().await?,
+            Self::Pull(s) => s.recv().await?,
+            Self::XPub(s) => s.recv().await?,
+            Self::Sub(s) => s.recv().await?,
+            Self::XSub(s) => s.recv().await?,
+            Self::Client(s) => s.recv().await?.into(),
+            Self::Gather(s) => s.recv().await?.into(),
+            Self::Channel(s) => s.recv().await?.into(),
+            Self::Server(s) => s
+                .recv()
+                .await
+                .map(|(id, body)| from(id.to_string(), body))?,
+            Self::Peer(s) => s
+                .recv()
+                .await
+                .map(|(id, body)| from(id.to_string(), body))?,
+            Self::Dish(s) => s.recv().await.map(|(group, body)| from(group, body))?,
+            Self::Push(_) | Self::Pub(_) | Self::Radio(_) | Self::Scatter(_) => {
+                std::future::pending().await
+            }
+        })
+    }
+}
+
+/// Send each request, then print its reply.
+async fn request(args: &Args) -> Result<(), Error> {
+    if args.bind {
+        return Err("REQ sockets only connect".into());
+    }
+    let (host, port) = &args.endpoint;
+    let mut req = Zmtp::connect_with(host, *port, args.options()).await?;
+    let mut outgoing = args.outgoing();
+    while let Some(msg) = outgoing.next().await {
+        futures::SinkExt::send(&mut req, msg?).await?;
+        match req.next().await {
+            Some(reply) => args.print(&reply?),
+            None => return Err("connection closed before the reply".into()),
+        }
+    }
+    discarded(req.close().await?)
+}
+
+/// Print each request, then reply with the next message to send.
+async fn reply(args: &Args) -> Result<(), Error> {
+    let mut rep = Rep::with_options(args.options());
+    let (host, port) = &args.endpoint;
+    if args.bind {
+        rep.bind(host, *port).await?;
+    } else {
+        rep.connect(host, *port).await?;
+    }
+    let mut outgoing = args.outgoing();
+    for _ in 0..args.count.unwrap_or(usize::MAX) {
+        args.print(&rep.recv().await?);
+        match outgoing.next().await {
+            Some(msg) => rep.send(msg?).await?,
+            None => break,
+        }
+    }
+    discarded(rep.close().await)
+}
+
+/// Fail if messages couldn't be sent before closing the socket.
+fn discarded(count: usize) -> Result<(), Error> {
+    match count {
+        0 => Ok(()),
+        count => Err(format!("{count} messages not sent within the linger time").into()),
+    }
+}
+
+/// Send the messages, printing the received ones meanwhile.
+async fn transfer(args: &Args, socket: &Socket) -> Result<(), Error> {
+    let sending = async {
+        let mut outgoing = args.outgoing();
+        while let Some(msg) = outgoing.next().await {
+            socket.send(msg?).await?;
+        }
+        Ok::<_, Error>(())
+    };
+    let receiving = async {
+        for _ in 0..args.count.unwrap_or(usize::MAX) {
+            args.print(&socket.recv().await?);
+        }
+        Ok::<_, Error>(())
+    };
+    match (socket.can_send(), socket.can_recv()) {
+        (true, false) => sending.await,
+        (false, _) => receiving.await,
+        // done once everything is sent and the expected messages are received
+        (true, true) => futures::try_join!(sending, receiving).map(drop),
+    }
+}
+
+#[main]
+async fn main() -> Result<(), Error> {
+    let args = Args::parse();
+    match args.socket_type {
+        SocketType::Req => return request(&args).await,
+        SocketType::Rep => return reply(&args).await,
+        _ => {}
+    }
+    let socket = Socket::new(&args)?;
+    socket.open(&args.endpoint, args.bind).await?;
+    transfer(&args, &socket).await?;
+    discarded(socket.close().await)
+}
@@ -0,0 +1,444 @@
+//! Decode the ZMTP connections of a pcap capture, as written by `tcpdump -w`.
+use bytes::{Bytes, BytesMut};
+use clap::Parser;
+use std::collections::HashMap;
+use std::fmt::Display;
+use std::io::Write;
+use std::net::SocketAddr;
+use std::path::PathBuf;
+use std::process::ExitCode;
+use std::time::Duration;
+use zmtp::errors::ParseError;
+use zmtp::packets::null::{Command, Frame};
+use zmtp::packets::{FrameType, Greeting, Mechanism, RawFrame};
+use zmtp::sockets::SocketType;
+
+mod pcap;
+mod tcp;
+
+/// Print the greetings, handshake commands and message frames of the ZMTP connections of a
+/// pcap file, flagging the protocol violations.
+///
+/// Exit with a failure status when the capture has protocol violations.
+#[derive(Debug, Parser)]
+#[command(name = "zmtp-dissect")]
+struct Args {
+    /// The pcap file
+    file: PathBuf,
+    /// Only decode the connections from or to this TCP port
+    #[arg(short, long)]
+    port: Vec<u16>,
+    /// Print at most this many bytes of each frame
+    #[arg(long, default_value_t = 32)]
+    max_body: usize,
+}
+
+/// How far the decoding of a direction went.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum State {
+    Greeting,
+    /// The NULL handshake, until the READY command.
+    Handshake,
+    /// The messages, after the READY command.
+    Traffic,
+    /// The commands of another security mechanism, whose messages are opaque.
+    Secured,
+    /// A violation made the rest of the direction undecodable.
+    Lost,
+}
+
+/// One direction of a connection.
+struct Side {
+    src: SocketAddr,
+    dst: SocketAddr,
+    stream: Option<tcp::Stream>,
+    buf: BytesMut,
+    state: State,
+    socket_type: Option<SocketType>,
+    /// The number of frames of the message being received.
+    frames: usize,
+}
+
+impl Side {
+    fn new(src: SocketAddr, dst: SocketAddr) -> Self {
+        Self {
+            src,
+            dst,
+            stream: None,
+            buf: BytesMut::new(),
+            state: State::Greeting,
+            socket_type: None,
+            frames: 0,
+        }
+    }
+}
+
+/// The printer of the decoded events to `out`, counting the violations.
+struct Printer<W> {
+    out: W,
+    max_body: usize,
+    violations: usize,
+}
+
+impl<W: Write> Printer<W> {
+    fn event(&mut self, time: Duration, side: &Side, event: impl Display) {
+        let (secs, micros) = (time.as_secs(), time.subsec_micros());
+        writeln!(
+            self.out,
+            "{secs}.{micros:06} {} -> {} {event}",
+            side.src, side.dst
+        )
+        .expect("can't write the decoded events");
+    }
+
+    fn violation(&mut self, time: Duration, side: &Side, violation: impl Display) {
+        self.violations += 1;
+        self.event(time, side, format_args!("VIOLATION {violation}"));
+    }
+
+    /// The escaped start of a frame body.
+    fn body(&self, body: &[u8]) -> String {
+        let preview = body[..body.len().min(self.max_body)].escape_ascii();
+        match body.len() > self.max_body {
+            true => format!("\"{preview}\"..."),
+            false => format!("\"{preview}\""),
+        }
+    }
+
+    /// Decode the bytes the `side` sent, with the socket type of the other side.
+    fn decode(&mut self, time: Duration, side: &mut Side, peer: Option<SocketType>) {
+        loop {
+            let decoded = match side.state {
+                State::Greeting => self.greeting(time, side),
+                State::Handshake | State::Traffic => self.null_frame(time, side, peer),
+                State::Secured => self.secured_frame(time, side),
+                State::Lost => {
+                    side.buf.clear();
+                    false
+                }
+            };
+            if !decoded {
+                return;
+            }
+        }
+    }
+
+    /// Decode the greeting, returning whether it was fully received.
+    fn greeting(&mut self, time: Duration, side: &mut Side) -> bool {
+        // the signature is sent alone first: fail early on peers other than ZMTP ones
+        if side.buf.first().is_some_and(|byte| *byte != 0xff) {
+            self.violation(time, side, "not a ZMTP signature");
+            side.state = State::Lost;
+            return true;
+        }
+        if side.buf.len() < Greeting::LEN {
+            return false;
+        }
+        let greeting = match Greeting::parse(&side.buf) {
+            Ok(greeting) => greeting,
+            Err(err) => {
+                self.violation(time, side, err);
+                side.state = State::Lost;
+                return true;
+            }
+        };
+        let _ = side.buf.split_to(Greeting::LEN);
+        let (version, mechanism) = (greeting.version(), greeting.mechanism());
+        let name = String::from_utf8_lossy(&mechanism.0);
+        self.event(
+            time,
+            side,
+            format_args!(
+                "greeting ZMTP {}.{} {} as-server={}",
+                version.major,
+                version.minor,
+                name.trim_end_matches('\0'),
+                greeting.as_server()
+            ),
+        );
+        if version.major < 3 {
+            self.violation(time, side, "ZMTP versions before 3.0 are not supported");
+            side.state = State::Lost;
+        } else if mechanism == Mechanism::NULL {
+            side.state = State::Handshake;
+        } else {
+            self.event(time, side, "messages of this mechanism are not decoded");
+            side.state = State::Secured;
+        }
+        true
+    }
+
+    /// Decode a frame of the NULL mechanism, returning whether it was fully received.
+    fn null_frame(&mut self, time: Duration, side: &mut Side, peer: Option<SocketType>) -> bool {
+        let raw = match RawFrame::decode(&mut side.buf) {
+            Ok(Some(raw)) => raw,
+            Ok(None) => return false,
+            Err(err) => {
+                self.violation(time, side, err);
+                side.state = State::Lost;
+                return true;
+            }
+        };
+        let is_command = matches!(raw, RawFrame::Command(_));
+        if is_command && side.frames > 0 {
+            self.violation(time, side, "command inside a multipart message");
+            side.frames = 0;
+        }
+        match Frame::try_from(raw) {
+            Ok(Frame::Command(command)) => self.command(time, side, peer, command),
+            Ok(frame) => self.message_frame(time, side, frame),
+            Err(ParseError::UnknownCommand(name)) => {
+                self.event(time, side, format_args!("{name} command not decoded"))
+            }
+            // the frame is complete: the next one can still be decoded
+            Err(err) => self.violation(time, side, err),
+        }
+        true
+    }
+
+    fn command(
+        &mut self,
+        time: Duration,
+        side: &mut Side,
+        peer: Option<SocketType>,
+        command: Command,
+    ) {
+        match command {
+            Command::Ready {
+                socket_type,
+                identity,
+            } => {
+                let identity = match identity {
+                    Some(identity) => format!(" Identity={}", self.body(&identity)),
+                    None => String::new(),
+                };
+                self.event(
+                    time,
+                    side,
+                    format_args!("READY Socket-Type={}{identity}", socket_type.escape_ascii()),
+                );
+                if side.state == State::Traffic {
+                    self.violation(time, side, "READY command sent twice");
+                    return;
+                }
+                side.state = State::Traffic;
+                side.socket_type = SocketType::from_name(&socket_type);
+                match (side.socket_type, peer) {
+                    (None, _) => self.violation(time, side, "unknown socket type"),
+                    (Some(local), Some(peer)) if !local.is_compatible(peer) => {
+                        let violation = format!("{local} socket connected to a {peer} socket");
+                        self.violation(time, side, violation)
+                    }
+                    _ => (),
+                }
+            }
+            Command::Error(reason) => {
+                self.event(time, side, format_args!("ERROR {:?}", reason));
+            }
+            Command::Join(_) | Command::Leave(_) if side.state != State::Traffic => {
+                self.violation(time, side, "JOIN or LEAVE command before READY");
+                side.state = State::Traffic;
+            }
+            Command::Join(group) => {
+                self.event(time, side, format_args!("JOIN {}", self.body(&group)));
+                if side.socket_type != Some(SocketType::Dish) {
+                    self.violation(time, side, "JOIN command from a socket other than DISH");
+                }
+            }
+            Command::Leave(group) => {
+                self.event(time, side, format_args!("LEAVE {}", self.body(&group)));
+                if side.socket_type != Some(SocketType::Dish) {
+                    self.violation(time, side, "LEAVE command from a socket other than DISH");
+                }
+            }
+        }
+    }
+
+    fn message_frame(&mut self, time: Duration, side: &mut Side, frame: Frame) {
+        if side.state != State::Traffic {
+            self.violation(time, side, "message before the READY command");
+            side.state = State::Traffic;
+        }
+        let (body, more) = match frame {
+            Frame::More(body) => (body, true),
+            Frame::Separator => (Bytes::new(), true),
+            Frame::Message(body) => (body, false),
+            Frame::Command(_) => unreachable!("commands are decoded apart"),
+        };
+        let mut event = format!(
+            "message frame {} of {} bytes{} {}",
+            side.frames + 1,
+            body.len(),
+            if more { " (more)" } else { "" },
+            self.body(&body)
+        );
+        // subscriptions are single frame messages from the subscribers
+        let subscriber = matches!(side.socket_type, Some(SocketType::Sub | SocketType::XSub));
+        if subscriber && side.frames == 0 && !more {
+            match body.first() {
+                Some(1) => event += &format!(" subscribe {}", self.body(&body[1..])),
+                Some(0) => event += &format!(" unsubscribe {}", self.body(&body[1..])),
+                _ => (),
+            }
+        }
+        self.event(time, side, event);
+        side.frames = if more { side.frames + 1 } else { 0 };
+    }
+
+    /// Print the kind and size of a frame of another mechanism, returning whether it was fully
+    /// received.
+    fn secured_frame(&mut self, time: Duration, side: &mut Side) -> bool {
+        let (header, header_len) = match FrameType::parse(&side.buf) {
+            Ok(Some(header)) => header,
+            Ok(None) => return false,
+            Err(err) => {
+                self.violation(time, side, err);
+                side.state = State::Lost;
+                return true;
+            }
+        };
+        let len = header_len as u64 + header.size;
+        if (side.buf.len() as u64) < len {
+            return false;
+        }
+        let body = side.buf.split_to(len as usize).split_off(header_len);
+        if header.flags.is_command() {
+            let name_len = body.first().map_or(0, |len| usize::from(*len));
+            let name = body[body.len().min(1)..body.len().min(1 + name_len)].escape_ascii();
+            self.event(
+                time,
+                side,
+                format_args!("{name} command of {} bytes", body.len()),
+            );
+        } else {
+            let more = if header.flags.is_more() {
+                " (more)"
+            } else {
+                ""
+            };
+            self.event(
+                time,
+                side,
+                format_args!("message frame of {} bytes{more}", body.len()),
+            );
+        }
+        true
+    }
+}
+
+/// The two directions of a connection, the one of the lowest address first.
+type Connection = [Side; 2];
+
+/// Print the events of the `capture` to `out`, returning the number of violations.
+fn dissect(args: &Args, capture: pcap::Capture, out: impl Write) -> Result<usize, String> {
+    let mut printer = Printer {
+        out,
+        max_body: args.max_body,
+        violations: 0,
+    };
+    let mut connections: HashMap<(SocketAddr, SocketAddr), Connection> = HashMap::new();
+    let mut end = Duration::ZERO;
+    for segment in capture {
+        let segment = segment?;
+        end = segment.time;
+        let (src, dst) = (segment.src, segment.dst);
+        let ports = [src.port(), dst.port()];
+        if !args.port.is_empty() && !ports.iter().any(|port| args.port.contains(port)) {
+            continue;
+        }
+        let key = (src.min(dst), src.max(dst));
+        let connection = connections
+            .entry(key)
+            .or_insert_with(|| [Side::new(key.0, key.1), Side::new(key.1, key.0)]);
+        let (index, time) = (usize::from(src != key.0), segment.time);
+        let peer = connection[1 - index].socket_type;
+        let side = &mut connection[index];
+
+        // the SYN takes a sequence number of its own
+        let seq = segment.seq.wrapping_add(segment.syn.into());
+        if side.stream.is_none() && !segment.syn {
+            printer.event(time, side, "capture starts mid-connection");
+        }
+        let stream = side.stream.get_or_insert_with(|| tcp::Stream::new(seq));
+        for bytes in stream.push(seq, segment.payload) {
+            side.buf.extend_from_slice(&bytes);
+        }
+        printer.decode(time, side, peer);
+        if segment.rst {
+            printer.event(time, side, "RST");
+        } else if segment.fin {
+            printer.event(time, side, "FIN");
+        }
+    }
+
+    let mut sides: Vec<_> = connections.values_mut().flatten().collect();
+    sides.sort_by_key(|side| (side.src, side.dst));
+    for side in sides {
+        if let Some(offset) = side.stream.as_ref().and_then(|stream| stream.gap()) {
+            let event = format!("capture lost the bytes from offset {offset}");
+            printer.event(end, side, event);
+        } else if !side.buf.is_empty() && side.state != State::Lost {
+            let event = format!("capture ends {} bytes into a frame", side.buf.len());
+            printer.event(end, side, event);
+        }
+    }
+    Ok(printer.violations)
+}
+
+fn main() -> ExitCode {
+    let args = Args::parse();
+    let capture = std::fs::read(&args.file)
+        .map_err(|err| format!("can't read {}: {err}", args.file.display()))
+        .and_then(|data| pcap::Capture::new(data.into()));
+    match capture.and_then(|capture| dissect(&args, capture, std::io::stdout().lock())) {
+        Ok(0) => ExitCode::SUCCESS,
+        Ok(violations) => {
+            eprintln!("{violations} protocol violation(s)");
+            ExitCode::FAILURE
+        }
+        Err(err) => {
+            eprintln!("zmtp-dissect: {err}");
+            ExitCode::from(2)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{dissect, pcap, Args};
+    use bytes::Bytes;
+    use clap::Parser;
+
+    /// A PUB and a SUB socket handshaking, then the SUB socket joining a group.
+    const VIOLATION: &[u8] = include_bytes!("violation.pcap");
+
+    #[test]
+    fn violation() {
+        let capture = pcap::Capture::new(Bytes::from_static(VIOLATION)).unwrap();
+        let args = Args::parse_from(["zmtp-dissect", "violation.pcap"]);
+        let mut out = Vec::new();
+        assert_eq!(dissect(&args, capture, &mut out), Ok(1));
+        let expected = [
+            "1700000000.000003 10.0.0.1:40000 -> 10.0.0.2:5555 greeting ZMTP 3.0 NULL as-server=false",
+            "1700000000.000004 10.0.0.2:5555 -> 10.0.0.1:40000 greeting ZMTP 3.0 NULL as-server=false",
+            "1700000000.000005 10.0.0.1:40000 -> 10.0.0.2:5555 READY Socket-Type=PUB",
+            "1700000000.000006 10.0.0.2:5555 -> 10.0.0.1:40000 READY Socket-Type=SUB",
+            "1700000000.000007 10.0.0.2:5555 -> 10.0.0.1:40000 JOIN \"news\"",
+            "1700000000.000007 10.0.0.2:5555 -> 10.0.0.1:40000 VIOLATION JOIN command from a socket other than DISH",
+            "1700000000.000008 10.0.0.1:40000 -> 10.0.0.2:5555 message frame 1 of 5 bytes \"hello\"",
+        ];
+        assert_eq!(
+            String::from_utf8(out).unwrap().lines().collect::<Vec<_>>(),
+            expected
+        );
+    }
+
+    #[test]
+    fn other_port() {
+        let capture = pcap::Capture::new(Bytes::from_static(VIOLATION)).unwrap();
+        let args = Args::parse_from(["zmtp-dissect", "violation.pcap", "--port", "5556"]);
+        let mut out = Vec::new();
+        assert_eq!(dissect(&args, capture, &mut out), Ok(0));
+        assert!(out.is_empty());
+    }
+}
@@ -0,0 +1,256 @@
+//! Just enough of pcap, Ethernet, IP and TCP to follow the TCP segments of a capture.
+use bytes::Bytes;
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr};
+use std::time::Duration;
+
+/// The link layers of the captured packets.
+#[derive(Debug, Clone, Copy)]
+enum Link {
+    /// BSD loopback: the address family on 4 bytes.
+    Null,
+    Ethernet,
+    /// Linux cooked capture, as `tcpdump -i any` writes.
+    LinuxSll,
+    LinuxSll2,
+    /// IP packets, without link layer.
+    Raw,
+}
+
+impl Link {
+    fn from_type(link_type: u32) -> Option<Self> {
+        Some(match link_type {
+            0 => Self::Null,
+            1 => Self::Ethernet,
+            113 => Self::LinuxSll,
+            276 => Self::LinuxSll2,
+            12 | 101 | 228 | 229 => Self::Raw,
+            _ => return None,
+        })
+    }
+
+    /// The IP packet of a captured frame.
+    fn ip<'a>(&self, frame: &'a [u8]) -> Option<&'a [u8]> {
+        match self {
+            Self::Null => frame.get(4..),
+            Self::Ethernet => {
+                let ether_type = |at: usize| frame.get(at..at + 2).map(|t| [t[0], t[1]]);
+                let mut at = 12;
+                // skip the 802.1Q VLAN tags
+                while ether_type(at)? == [0x81, 0x00] {
+                    at += 4;
+                }
+                match ether_type(at)? {
+                    [0x08, 0x00] | [0x86, 0xdd] => frame.get(at + 2..),
+                    _ => None,
+                }
+            }
+            Self::LinuxSll => frame.get(16..),
+            Self::LinuxSll2 => frame.get(20..),
+            Self::Raw => Some(frame),
+        }
+    }
+}
+
+/// A TCP segment of a captured packet.
+pub struct Segment {
+    pub time: Duration,
+    pub src: SocketAddr,
+    pub dst: SocketAddr,
+    pub seq: u32,
+    pub syn: bool,
+    pub fin: bool,
+    pub rst: bool,
+    pub payload: Bytes,
+}
+
+/// The TCP segment carried by an IP packet, sliced from the `capture`.
+fn segment(capture: &Bytes, time: Duration, ip: &[u8]) -> Option<Segment> {
+    let (src, dst, tcp): (IpAddr, IpAddr, _) = match ip.first()? >> 4 {
+        4 => {
+            let header_len = usize::from(ip[0] & 0x0f) * 4;
+            let total_len = usize::from(u16::from_be_bytes(ip.get(2..4)?.try_into().ok()?));
+            if *ip.get(9)? != 6 {
+                return None;
+            }
+            let src: [u8; 4] = ip.get(12..16)?.try_into().ok()?;
+            let dst: [u8; 4] = ip.get(16..20)?.try_into().ok()?;
+            // the frame may be padded past the IP packet
+            let tcp = ip.get(header_len..total_len.min(ip.len()))?;
+            (Ipv4Addr::from(src).into(), Ipv4Addr::from(dst).into(), tcp)
+        }
+        6 => {
+            let payload_len = usize::from(u16::from_be_bytes(ip.get(4..6)?.try_into().ok()?));
+            if *ip.get(6)? != 6 {
+                return None;
+            }
+            let src: [u8; 16] = ip.get(8..24)?.try_into().ok()?;
+            let dst: [u8; 16] = ip.get(24..40)?.try_into().ok()?;
+            let tcp = ip.get(40..(40 + payload_len).min(ip.len()))?;
+            (Ipv6Addr::from(src).into(), Ipv6Addr::from(dst).into(), tcp)
+        }
+        _ => return None,
+    };
+    let port = |at: usize| Some(u16::from_be_bytes(tcp.get(at..at + 2)?.try_into().ok()?));
+    let data_offset = usize::from(tcp.get(12)? >> 4) * 4;
+    let flags = *tcp.get(13)?;
+    Some(Segment {
+        time,
+        src: SocketAddr::new(src, port(0)?),
+        dst: SocketAddr::new(dst, port(2)?),
+        seq: u32::from_be_bytes(tcp.get(4..8)?.try_into().ok()?),
+        syn: flags & 0x02 != 0,
+        fin: flags & 0x01 != 0,
+        rst: flags & 0x04 != 0,
+        payload: capture.slice_ref(tcp.get(data_offset..)?),
+    })
+}
+
+/// The packets of a classic pcap file.
+pub struct Capture {
+    data: Bytes,
+    pos: usize,
+    /// Whether the file was written with the other byte order.
+    swapped: bool,
+    /// Whether the timestamps have nanoseconds rather than microseconds.
+    nanos: bool,
+    link: Link,
+}
+
+impl Capture {
+    const HEADER_LEN: usize = 24;
+    const RECORD_LEN: usize = 16;
+
+    pub fn new(data: Bytes) -> Result<Self, String> {
+        let magic: [u8; 4] = data
+            .get(..4)
+            .and_then(|magic| magic.try_into().ok())
+            .ok_or("not a pcap file")?;
+        let (swapped, nanos) = match u32::from_le_bytes(magic) {
+            0xa1b2c3d4 => (false, false),
+            0xa1b23c4d => (false, true),
+            0xd4c3b2a1 => (true, false),
+            0x4d3cb2a1 => (true, true),
+            0x0a0d0d0a => return Err("pcapng is not supported, convert the capture to pcap".into()),
+            _ => return Err("not a pcap file".into()),
+        };
+        let mut capture = Self {
+            data,
+            pos: Self::HEADER_LEN,
+            swapped,
+            nanos,
+            link: Link::Raw,
+        };
+        let link_type = capture.u32(20).ok_or("truncated pcap header")?;
+        capture.link = Link::from_type(link_type & 0x0fff_ffff)
+            .ok_or_else(|| format!("unsupported link type {link_type}"))?;
+        Ok(capture)
+    }
+
+    fn u32(&self, at: usize) -> Option<u32> {
+        let raw = self.data.get(at..at + 4)?.try_into().ok()?;
+        Some(match self.swapped {
+            false => u32::from_le_bytes(raw),
+            true => u32::from_be_bytes(raw),
+        })
+    }
+}
+
+impl Iterator for Capture {
+    type Item = Result<Segment, String>;
+
+    /// The next TCP segment, skipping the other packets.
+    ///
+    /// Fail on a truncated capture.
+    fn next(&mut self) -> Option<Self::Item> {
+        while self.pos < self.data.len() {
+            let record = self.pos;
+            let (Some(secs), Some(fraction), Some(len)) =
+                (self.u32(record), self.u32(record + 4), self.u32(record + 8))
+            else {
+                self.pos = self.data.len();
+                return Some(Err("truncated packet record".into()));
+            };
+            let start = record + Self::RECORD_LEN;
+            let end = start + len as usize;
+            if end > self.data.len() {
+                self.pos = self.data.len();
+                return Some(Err("truncated packet".into()));
+            }
+            self.pos = end;
+            let time = Duration::from_secs(secs.into())
+                + match self.nanos {
+                    true => Duration::from_nanos(fraction.into()),
+                    false => Duration::from_micros(fraction.into()),
+                };
+            let ip = self.link.ip(&self.data[start..end]);
+            if let Some(segment) = ip.and_then(|ip| segment(&self.data, time, ip)) {
+                return Some(Ok(segment));
+            }
+        }
+        None
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Capture;
+    use bytes::Bytes;
+    use std::time::Duration;
+
+    /// A raw IP capture of one TCP segment carrying `zmtp`, written in big endian when
+    /// `swapped`.
+    fn capture(magic: u32, swapped: bool, fraction: u32) -> Bytes {
+        let u32 = |n: u32| match swapped {
+            false => n.to_le_bytes(),
+            true => n.to_be_bytes(),
+        };
+        let mut packet = vec![
+            0x45, 0, 0, 44, 0, 0, 0, 0, 64, 6, 0, 0, 10, 0, 0, 1, 10, 0, 0, 2,
+        ];
+        packet.extend([0x9c, 0x40, 0x15, 0xb3]); // ports 40000 and 5555
+        packet.extend(1000_u32.to_be_bytes());
+        packet.extend([0, 0, 0, 0, 0x50, 0x18, 0xff, 0xff, 0, 0, 0, 0]);
+        packet.extend(b"zmtp");
+        let mut data = u32(magic).to_vec();
+        data.extend(match swapped {
+            false => [2, 0, 4, 0],
+            true => [0, 2, 0, 4],
+        });
+        data.extend([0; 8]);
+        data.extend(u32(65535));
+        data.extend(u32(101));
+        for field in [1, fraction, packet.len() as u32, packet.len() as u32] {
+            data.extend(u32(field));
+        }
+        data.extend(packet);
+        data.into()
+    }
+
+    #[test]
+    fn headers() {
+        let time = Duration::from_micros(1_000_250);
+        for (magic, swapped, fraction) in [
+            (0xa1b2c3d4, false, 250),
+            (0xa1b2c3d4, true, 250),
+            (0xa1b23c4d, false, 250_000),
+            (0xa1b23c4d, true, 250_000),
+        ] {
+            let mut segments = Capture::new(capture(magic, swapped, fraction)).unwrap();
+            let segment = segments.next().unwrap().unwrap();
+            assert_eq!(segment.time, time);
+            assert_eq!(segment.src, "10.0.0.1:40000".parse().unwrap());
+            assert_eq!(segment.dst, "10.0.0.2:5555".parse().unwrap());
+            assert_eq!(segment.seq, 1000);
+            assert_eq!(segment.payload, Bytes::from("zmtp"));
+            assert!(segments.next().is_none());
+        }
+    }
+
+    #[test]
+    fn truncated() {
+        let data = capture(0xa1b2c3d4, false, 0);
+        let mut segments = Capture::new(data.slice(..data.len() - 1)).unwrap();
+        assert_eq!(segments.next().unwrap().err().unwrap(), "truncated packet");
+        assert!(Capture::new(data.slice(..20)).is_err());
+    }
+}
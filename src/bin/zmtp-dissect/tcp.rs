@@ -0,0 +1,118 @@
+//! Reassembly of the bytes of one direction of a TCP connection.
+use bytes::Bytes;
+use std::collections::BTreeMap;
+
+/// The bytes one peer sent, put back in order.
+///
+/// Segments are kept until the bytes before them are captured: retransmitted and overlapping
+/// segments are trimmed to the bytes not yet delivered.
+pub struct Stream {
+    /// The sequence number of the next byte to deliver.
+    seq: u32,
+    /// The offset of the next byte to deliver, from the start of the stream.
+    offset: u64,
+    /// The segments received ahead of the next byte, by offset.
+    pending: BTreeMap<u64, Bytes>,
+}
+
+impl Stream {
+    /// A stream whose first byte has the sequence number `seq`.
+    pub fn new(seq: u32) -> Self {
+        Self {
+            seq,
+            offset: 0,
+            pending: BTreeMap::new(),
+        }
+    }
+
+    /// Add the `payload` of the segment starting at `seq`, and return the bytes now in order.
+    pub fn push(&mut self, seq: u32, payload: Bytes) -> Vec<Bytes> {
+        // sequence numbers wrap around: place the segment relatively to the next byte
+        let start = self.offset as i64 + i64::from(seq.wrapping_sub(self.seq) as i32);
+        let end = start + payload.len() as i64;
+        if end <= self.offset as i64 {
+            return Vec::new();
+        }
+        let (start, payload) = match start < self.offset as i64 {
+            true => (
+                self.offset,
+                payload.slice((self.offset as i64 - start) as usize..),
+            ),
+            false => (start as u64, payload),
+        };
+        let pending = self.pending.entry(start).or_default();
+        if pending.len() < payload.len() {
+            *pending = payload;
+        }
+
+        let mut ready = Vec::new();
+        while let Some(entry) = self.pending.first_entry() {
+            if *entry.key() > self.offset {
+                break;
+            }
+            let (start, payload) = entry.remove_entry();
+            let skip = (self.offset - start) as usize;
+            if skip < payload.len() {
+                let payload = payload.slice(skip..);
+                self.offset += payload.len() as u64;
+                self.seq = self.seq.wrapping_add(payload.len() as u32);
+                ready.push(payload);
+            }
+        }
+        ready
+    }
+
+    /// The offset of the first byte missing from the capture, if later bytes were captured.
+    pub fn gap(&self) -> Option<u64> {
+        self.pending.keys().next().map(|_| self.offset)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Stream;
+    use bytes::Bytes;
+
+    fn bytes(parts: &[&'static str]) -> Vec<Bytes> {
+        parts
+            .iter()
+            .map(|part| Bytes::from_static(part.as_bytes()))
+            .collect()
+    }
+
+    #[test]
+    fn out_of_order() {
+        let mut stream = Stream::new(100);
+        assert_eq!(stream.push(105, Bytes::from("fgh")), bytes(&[]));
+        assert_eq!(stream.gap(), Some(0));
+        assert_eq!(
+            stream.push(100, Bytes::from("abcde")),
+            bytes(&["abcde", "fgh"])
+        );
+        assert_eq!(stream.gap(), None);
+    }
+
+    #[test]
+    fn overlapping() {
+        let mut stream = Stream::new(100);
+        assert_eq!(stream.push(100, Bytes::from("abc")), bytes(&["abc"]));
+        // retransmitted, then partly new
+        assert_eq!(stream.push(100, Bytes::from("abc")), bytes(&[]));
+        assert_eq!(stream.push(101, Bytes::from("bcdef")), bytes(&["def"]));
+        // the longest of the segments ahead at the same place is kept
+        assert_eq!(stream.push(108, Bytes::from("ij")), bytes(&[]));
+        assert_eq!(stream.push(108, Bytes::from("ijkl")), bytes(&[]));
+        assert_eq!(stream.push(106, Bytes::from("ghi")), bytes(&["ghi", "jkl"]));
+    }
+
+    #[test]
+    fn wrapping() {
+        let mut stream = Stream::new(u32::MAX - 1);
+        assert_eq!(stream.push(u32::MAX - 1, Bytes::from("ab")), bytes(&["ab"]));
+        assert_eq!(stream.push(1, Bytes::from("d")), bytes(&[]));
+        assert_eq!(stream.push(0, Bytes::from("c")), bytes(&["c", "d"]));
+        // a retransmission from before the wrap
+        assert_eq!(stream.push(u32::MAX, Bytes::from("bc")), bytes(&[]));
+        assert_eq!(stream.push(2, Bytes::from("e")), bytes(&["e"]));
+    }
+}
@@ -0,0 +1,81 @@
+use serde::{Deserialize, Serialize};
+use tokio::main;
+use twelf::{config, Layer};
+use zmtp::sockets::{proxy, Dealer, Pub, Rep, Router, XPub, XSub};
+
+/// The sockets the proxy shuttles messages between.
+#[derive(Debug, Clone, Copy, Default, Deserialize, Serialize)]
+#[serde(rename_all = "kebab-case")]
+enum Kind {
+    /// A ROUTER frontend for the clients, and a DEALER backend for the workers.
+    #[default]
+    Queue,
+    /// An XSUB frontend for the publishers, and an XPUB backend for the subscribers.
+    Forwarder,
+}
+
+#[config]
+struct Conf {
+    /// The sockets of the proxy: queue (ROUTER and DEALER) or forwarder (XSUB and XPUB)
+    #[serde(default)]
+    kind: Kind,
+    /// The IP address to bind the sockets on
+    host: String,
+    /// The port of the frontend socket
+    frontend_port: u16,
+    /// The port of the backend socket
+    backend_port: u16,
+    /// The port of a PUB socket publishing every message going through the proxy
+    capture_port: Option<u16>,
+    /// The port of a REP socket receiving PAUSE, RESUME, TERMINATE and STATISTICS commands, replying to each
+    control_port: Option<u16>,
+}
+
+/// Bind `socket` on the `port`, if any.
+macro_rules! bind_on {
+    ($socket:expr, $host:expr, $port:expr) => {
+        match $port {
+            Some(port) => {
+                let socket = $socket;
+                socket.bind($host, port).await?;
+                Some(socket)
+            }
+            None => None,
+        }
+    };
+}
+
+#[main]
+async fn main() -> Result<(), Box<dyn std::error::Error>> {
+    // Will generate global arguments for each of your fields inside your configuration struct
+    let app = clap::Command::new("zmtp-proxy").args(Conf::clap_args());
+
+    // Init configuration with layers, each layers override only existing fields
+    let config = Conf::with_layers(&[
+        Layer::Toml("zmtp-proxy.toml".into()),
+        Layer::Env(Some("ZMTP_PROXY_".to_string())),
+        Layer::Clap(app.get_matches()),
+    ])?;
+    let host = config.host.as_str();
+    let capture = bind_on!(Pub::new(), host, config.capture_port);
+    let control = bind_on!(Rep::new(), host, config.control_port);
+    eprintln!(
+        "Proxying tcp://{host}:{} and tcp://{host}:{}...",
+        config.frontend_port, config.backend_port
+    );
+    match config.kind {
+        Kind::Queue => {
+            let (frontend, backend) = (Router::new(), Dealer::new());
+            frontend.bind(host, config.frontend_port).await?;
+            backend.bind(host, config.backend_port).await?;
+            proxy(frontend, backend, capture, control).await?;
+        }
+        Kind::Forwarder => {
+            let (frontend, backend) = (XSub::new(), XPub::new());
+            frontend.bind(host, config.frontend_port).await?;
+            backend.bind(host, config.backend_port).await?;
+            proxy(frontend, backend, capture, control).await?;
+        }
+    }
+    Ok(())
+}
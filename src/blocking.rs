@@ -0,0 +1,519 @@
+//! Synchronous sockets, for programs without an async runtime.
+//!
+//! Each socket mirrors the async socket of the same name in [`sockets`](crate::sockets): the
+//! connections are handled by a runtime shared by the blocking sockets, running on a
+//! background thread, and every method blocks the calling thread until it completes.
+//!
+//! Sending and receiving wait forever by default. Give a timeout with `with_send_timeout` and
+//! `with_recv_timeout` to fail with [`SocketError::TimedOut`] instead.
+//!
+//! The methods must not be called from an async task: use the async sockets there.
+//!
+//! # Exemple
+//!
+//! ```rust,no_run
+//! use bytes::Bytes;
+//! use std::time::Duration;
+//! use zmtp::blocking::Zmtp;
+//!
+//! let mut req = Zmtp::connect("localhost", 55555)?.with_recv_timeout(Duration::from_secs(1));
+//! req.send(Bytes::from("ping"))?;
+//! let reply = req.recv()?;
+//! # Ok::<(), zmtp::Error>(())
+//! ```
+use crate::errors::{ConnectionError, SocketError};
+use crate::packets::Greeting;
+use crate::sockets::{self, Monitor, Options, RoutingId};
+use crate::{Multipart, Result};
+
+use bytes::Bytes;
+use futures::{Future, FutureExt, SinkExt, StreamExt};
+use std::net::SocketAddr;
+use std::time::Duration;
+
+/// The runtime handling the connections of every blocking socket.
+#[cfg(feature = "tokio")]
+fn runtime() -> &'static tokio::runtime::Runtime {
+    static RUNTIME: std::sync::OnceLock<tokio::runtime::Runtime> = std::sync::OnceLock::new();
+    RUNTIME.get_or_init(|| {
+        tokio::runtime::Builder::new_multi_thread()
+            .worker_threads(1)
+            .thread_name("zmtp-blocking")
+            .enable_all()
+            .build()
+            .expect("can't start the runtime of the blocking sockets")
+    })
+}
+
+/// Run `future` to completion, failing after `timeout`.
+///
+/// The async-std and smol runtimes handle the connections on their own threads, so the futures
+/// are run right on the calling thread without them.
+fn block_on<T>(timeout: Option<Duration>, future: impl Future<Output = Result<T>>) -> Result<T> {
+    let future = async {
+        match timeout {
+            Some(timeout) => crate::runtime::timeout(timeout, future)
+                .await
+                .unwrap_or_else(|| Err(SocketError::TimedOut.into())),
+            None => future.await,
+        }
+    };
+    #[cfg(feature = "tokio")]
+    return runtime().block_on(future);
+    #[cfg(not(feature = "tokio"))]
+    futures::executor::block_on(future)
+}
+
+/// Define a blocking socket wrapping the async socket of the same name, with its constructors,
+/// timeouts, `connect`, `bind`, `monitor` and `close` methods.
+macro_rules! blocking_socket {
+    ($(#[$doc:meta])* $name:ident, connect -> $connected:ty) => {
+        $(#[$doc])*
+        pub struct $name {
+            socket: sockets::$name,
+            send_timeout: Option<Duration>,
+            recv_timeout: Option<Duration>,
+        }
+
+        impl Default for $name {
+            fn default() -> Self {
+                Self::new()
+            }
+        }
+
+        impl $name {
+            pub fn new() -> Self {
+                Self::with_options(Options::default())
+            }
+
+            pub fn with_options(options: Options) -> Self {
+                Self {
+                    socket: sockets::$name::with_options(options),
+                    send_timeout: None,
+                    recv_timeout: None,
+                }
+            }
+
+            /// Fail sending a message after `timeout`.
+            pub fn with_send_timeout(mut self, timeout: Duration) -> Self {
+                self.send_timeout = Some(timeout);
+                self
+            }
+
+            /// Fail receiving a message after `timeout`.
+            pub fn with_recv_timeout(mut self, timeout: Duration) -> Self {
+                self.recv_timeout = Some(timeout);
+                self
+            }
+
+            /// Connect to `tcp://host:port`.
+            pub fn connect(&self, host: &str, port: u16) -> Result<$connected> {
+                block_on(None, self.socket.connect(host, port))
+            }
+
+            /// Accept peers on `tcp://host:port`, returning the bound address.
+            pub fn bind(&self, host: &str, port: u16) -> Result<SocketAddr> {
+                block_on(None, self.socket.bind(host, port))
+            }
+
+            /// Return the monitor publishing the events of this socket.
+            pub fn monitor(&self) -> Monitor {
+                self.socket.monitor()
+            }
+
+            /// Close the socket, sending the queued messages first.
+            ///
+            #[doc = concat!("See [`sockets::", stringify!($name), "::close`].")]
+            pub fn close(self) -> usize {
+                // without a timeout, closing can't fail
+                block_on(None, self.socket.close().map(Ok)).unwrap_or_default()
+            }
+        }
+    };
+}
+
+/// A blocking REQ socket, see [`sockets::Zmtp`].
+pub struct Zmtp {
+    socket: sockets::Zmtp,
+    send_timeout: Option<Duration>,
+    recv_timeout: Option<Duration>,
+}
+
+impl Zmtp {
+    /// Connect to the REP or ROUTER socket at `tcp://host:port`.
+    pub fn connect(host: &str, port: u16) -> Result<Self> {
+        Self::connect_with(host, port, Options::default())
+    }
+
+    /// Connect to `tcp://host:port` with the given options.
+    pub fn connect_with(host: &str, port: u16, options: Options) -> Result<Self> {
+        Ok(Self {
+            socket: block_on(None, sockets::Zmtp::connect_with(host, port, options))?,
+            send_timeout: None,
+            recv_timeout: None,
+        })
+    }
+
+    /// Fail sending a request after `timeout`.
+    ///
+    /// A request timing out stays queued, and is sent before the next one.
+    pub fn with_send_timeout(mut self, timeout: Duration) -> Self {
+        self.send_timeout = Some(timeout);
+        self
+    }
+
+    /// Fail receiving a reply after `timeout`.
+    pub fn with_recv_timeout(mut self, timeout: Duration) -> Self {
+        self.recv_timeout = Some(timeout);
+        self
+    }
+
+    /// Return the greeting the peer sent during the handshake.
+    pub fn peer_greeting(&self) -> &Greeting {
+        self.socket.peer_greeting()
+    }
+
+    /// Return the monitor publishing the events of this socket.
+    pub fn monitor(&self) -> Monitor {
+        self.socket.monitor()
+    }
+
+    /// Send a request.
+    pub fn send(&mut self, msg: impl Into<Multipart>) -> Result<()> {
+        block_on(self.send_timeout, self.socket.send(msg.into()))
+    }
+
+    /// Receive the reply to the last request.
+    pub fn recv(&mut self) -> Result<Multipart> {
+        block_on(self.recv_timeout, async {
+            self.socket.next().await.unwrap_or_else(|| {
+                let err = std::io::Error::new(
+                    std::io::ErrorKind::UnexpectedEof,
+                    "connection closed by the peer",
+                );
+                Err(ConnectionError::from(err).into())
+            })
+        })
+    }
+
+    /// Close the socket, sending the queued requests first.
+    ///
+    /// See [`sockets::Zmtp::close`].
+    pub fn close(self) -> Result<usize> {
+        block_on(None, self.socket.close())
+    }
+}
+
+blocking_socket! {
+    /// A blocking REP socket, see [`sockets::Rep`].
+    Rep, connect -> ()
+}
+
+impl Rep {
+    /// Receive the next request from any peer, without its envelope.
+    pub fn recv(&mut self) -> Result<Multipart> {
+        block_on(self.recv_timeout, self.socket.recv())
+    }
+
+    /// Send the reply to the last received request.
+    pub fn send(&mut self, msg: impl Into<Multipart>) -> Result<()> {
+        block_on(self.send_timeout, self.socket.send(msg))
+    }
+}
+
+blocking_socket! {
+    /// A blocking DEALER socket, see [`sockets::Dealer`].
+    Dealer, connect -> ()
+}
+
+impl Dealer {
+    /// Send a message to the next peer in turn.
+    pub fn send(&self, msg: impl Into<Multipart>) -> Result<()> {
+        block_on(self.send_timeout, self.socket.send(msg))
+    }
+
+    /// Receive the next message from any peer.
+    pub fn recv(&self) -> Result<Multipart> {
+        block_on(self.recv_timeout, self.socket.recv())
+    }
+}
+
+blocking_socket! {
+    /// A blocking ROUTER socket, see [`sockets::Router`].
+    Router, connect -> ()
+}
+
+impl Router {
+    /// Send a message to the peer identified by its first frame.
+    pub fn send(&self, msg: impl Into<Multipart>) -> Result<()> {
+        block_on(self.send_timeout, self.socket.send(msg))
+    }
+
+    /// Receive the next message from any peer, prefixed by the frame identifying the peer.
+    pub fn recv(&self) -> Result<Multipart> {
+        block_on(self.recv_timeout, self.socket.recv())
+    }
+}
+
+blocking_socket! {
+    /// A blocking PUB socket, see [`sockets::Pub`].
+    Pub, connect -> ()
+}
+
+impl Pub {
+    /// Publish a message to the subscribed peers, never waiting.
+    pub fn send(&self, msg: impl Into<Multipart>) {
+        self.socket.send(msg)
+    }
+}
+
+blocking_socket! {
+    /// A blocking XPUB socket, see [`sockets::XPub`].
+    XPub, connect -> ()
+}
+
+impl XPub {
+    /// Publish a message to the subscribed peers, never waiting.
+    pub fn send(&self, msg: impl Into<Multipart>) {
+        self.socket.send(msg)
+    }
+
+    /// Receive the next subscription or cancellation of the peers.
+    pub fn recv(&self) -> Result<Multipart> {
+        block_on(self.recv_timeout, self.socket.recv())
+    }
+}
+
+blocking_socket! {
+    /// A blocking SUB socket, see [`sockets::Sub`].
+    Sub, connect -> ()
+}
+
+impl Sub {
+    /// Receive the messages starting with `topic`, the empty topic matching every message.
+    pub fn subscribe(&self, topic: impl AsRef<[u8]>) {
+        self.socket.subscribe(topic)
+    }
+
+    /// Stop receiving the messages of a subscribed `topic`.
+    pub fn unsubscribe(&self, topic: impl AsRef<[u8]>) {
+        self.socket.unsubscribe(topic)
+    }
+
+    /// Receive the next message matching a subscription.
+    pub fn recv(&self) -> Result<Multipart> {
+        block_on(self.recv_timeout, self.socket.recv())
+    }
+}
+
+blocking_socket! {
+    /// A blocking XSUB socket, see [`sockets::XSub`].
+    XSub, connect -> ()
+}
+
+impl XSub {
+    /// Send a subscription or a cancellation to the current and future publishers.
+    pub fn send(&self, msg: impl Into<Multipart>) {
+        self.socket.send(msg)
+    }
+
+    /// Receive the next message matching a subscription.
+    pub fn recv(&self) -> Result<Multipart> {
+        block_on(self.recv_timeout, self.socket.recv())
+    }
+}
+
+blocking_socket! {
+    /// A blocking PUSH socket, see [`sockets::Push`].
+    Push, connect -> ()
+}
+
+impl Push {
+    /// Send a message to the next puller in turn.
+    pub fn send(&self, msg: impl Into<Multipart>) -> Result<()> {
+        block_on(self.send_timeout, self.socket.send(msg))
+    }
+}
+
+blocking_socket! {
+    /// A blocking PULL socket, see [`sockets::Pull`].
+    Pull, connect -> ()
+}
+
+impl Pull {
+    /// Receive the next message from any pusher.
+    pub fn recv(&self) -> Result<Multipart> {
+        block_on(self.recv_timeout, self.socket.recv())
+    }
+}
+
+blocking_socket! {
+    /// A blocking CLIENT socket, see [`sockets::Client`].
+    Client, connect -> ()
+}
+
+impl Client {
+    /// Send a single-part message to the next server in turn.
+    pub fn send(&self, msg: impl Into<Multipart>) -> Result<()> {
+        block_on(self.send_timeout, self.socket.send(msg))
+    }
+
+    /// Receive the next message from any server.
+    pub fn recv(&self) -> Result<Bytes> {
+        block_on(self.recv_timeout, self.socket.recv())
+    }
+}
+
+blocking_socket! {
+    /// A blocking SERVER socket, see [`sockets::Server`].
+    Server, connect -> ()
+}
+
+impl Server {
+    /// Send a single-part message to the client with the `routing_id`.
+    pub fn send(&self, routing_id: RoutingId, msg: impl Into<Multipart>) -> Result<()> {
+        block_on(self.send_timeout, self.socket.send(routing_id, msg))
+    }
+
+    /// Receive the next message from any client, along with the client routing id.
+    pub fn recv(&self) -> Result<(RoutingId, Bytes)> {
+        block_on(self.recv_timeout, self.socket.recv())
+    }
+}
+
+blocking_socket! {
+    /// A blocking RADIO socket, see [`sockets::Radio`].
+    Radio, connect -> ()
+}
+
+impl Radio {
+    /// Send a single-part message to the dishes which joined `group`.
+    pub fn send(&self, group: &str, msg: impl Into<Multipart>) -> Result<()> {
+        block_on(self.send_timeout, self.socket.send(group, msg))
+    }
+}
+
+blocking_socket! {
+    /// A blocking DISH socket, see [`sockets::Dish`].
+    Dish, connect -> ()
+}
+
+impl Dish {
+    /// Receive the messages published to `group`, from the radios connected now and later.
+    pub fn join(&self, group: &str) -> Result<()> {
+        self.socket.join(group)
+    }
+
+    /// Stop receiving the messages published to `group`.
+    pub fn leave(&self, group: &str) -> Result<()> {
+        self.socket.leave(group)
+    }
+
+    /// Receive the next message of a joined group, along with its group.
+    pub fn recv(&self) -> Result<(String, Bytes)> {
+        block_on(self.recv_timeout, self.socket.recv())
+    }
+}
+
+blocking_socket! {
+    /// A blocking SCATTER socket, see [`sockets::Scatter`].
+    Scatter, connect -> ()
+}
+
+impl Scatter {
+    /// Send a single-part message to the next gather in turn.
+    pub fn send(&self, msg: impl Into<Multipart>) -> Result<()> {
+        block_on(self.send_timeout, self.socket.send(msg))
+    }
+}
+
+blocking_socket! {
+    /// A blocking GATHER socket, see [`sockets::Gather`].
+    Gather, connect -> ()
+}
+
+impl Gather {
+    /// Receive the next message from any scatter.
+    pub fn recv(&self) -> Result<Bytes> {
+        block_on(self.recv_timeout, self.socket.recv())
+    }
+}
+
+blocking_socket! {
+    /// A blocking CHANNEL socket, see [`sockets::Channel`].
+    Channel, connect -> ()
+}
+
+impl Channel {
+    /// Send a single-part message to the peer.
+    pub fn send(&self, msg: impl Into<Multipart>) -> Result<()> {
+        block_on(self.send_timeout, self.socket.send(msg))
+    }
+
+    /// Receive the next message from the peer.
+    pub fn recv(&self) -> Result<Bytes> {
+        block_on(self.recv_timeout, self.socket.recv())
+    }
+}
+
+blocking_socket! {
+    /// A blocking PEER socket, see [`sockets::Peer`].
+    ///
+    /// Connecting returns the routing id of the connected peer.
+    Peer, connect -> RoutingId
+}
+
+impl Peer {
+    /// Send a single-part message to the peer with the `routing_id`.
+    pub fn send(&self, routing_id: RoutingId, msg: impl Into<Multipart>) -> Result<()> {
+        block_on(self.send_timeout, self.socket.send(routing_id, msg))
+    }
+
+    /// Receive the next message from any peer, along with the peer routing id.
+    pub fn recv(&self) -> Result<(RoutingId, Bytes)> {
+        block_on(self.recv_timeout, self.socket.recv())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Pull, Push, Rep, Zmtp};
+    use crate::errors::SocketError;
+    use crate::Error;
+    use bytes::Bytes;
+    use std::time::Duration;
+
+    #[test]
+    fn req_rep() {
+        let mut rep = Rep::new();
+        let addr = rep.bind("127.0.0.1", 0).unwrap();
+        let replier = std::thread::spawn(move || {
+            let request = rep.recv().unwrap();
+            rep.send(request).unwrap();
+        });
+        let mut req = Zmtp::connect("127.0.0.1", addr.port()).unwrap();
+        req.send(Bytes::from("ping")).unwrap();
+        assert_eq!(req.recv().unwrap(), Bytes::from("ping").into());
+        replier.join().unwrap();
+    }
+
+    #[test]
+    fn timeouts() {
+        let pull = Pull::new().with_recv_timeout(Duration::from_millis(50));
+        let addr = pull.bind("127.0.0.1", 0).unwrap();
+        assert!(matches!(
+            pull.recv(),
+            Err(Error::Socket(SocketError::TimedOut))
+        ));
+
+        // nothing to push to
+        let push = Push::new().with_send_timeout(Duration::from_millis(50));
+        assert!(matches!(
+            push.send(Bytes::from("a")),
+            Err(Error::Socket(SocketError::TimedOut))
+        ));
+        push.connect("127.0.0.1", addr.port()).unwrap();
+        push.send(Bytes::from("b")).unwrap();
+        assert_eq!(pull.recv().unwrap(), Bytes::from("b").into());
+    }
+}
@@ -0,0 +1,793 @@
+//! The ZMTP protocol of a connection, free of any IO.
+//!
+//! A [`Connection`] is given the bytes received from the peer, and gives back the bytes to send
+//! along with the [`Event`]s of the connection: it runs the handshake of the NULL mechanism,
+//! then frames the messages. The sockets drive it over their transport, and so can custom event
+//! loops.
+//!
+//! # Exemple
+//!
+//! ```rust
+//! use zmtp::connection::{Connection, Event};
+//! use zmtp::packets::null::Frame;
+//! use zmtp::sockets::SocketType;
+//!
+//! let mut req = Connection::new(SocketType::Req);
+//! let mut rep = Connection::new(SocketType::Rep);
+//! req.send([Frame::Separator, Frame::Message("ping".into())]);
+//!
+//! // a real event loop writes the bytes to the socket, and reads the peer ones back
+//! let mut frames = Vec::new();
+//! while !(req.transmit().is_empty() && rep.transmit().is_empty()) {
+//!     rep.receive(&req.transmit().take());
+//!     req.receive(&rep.transmit().take());
+//!     while let Some(event) = rep.poll_event() {
+//!         if let Event::Frame(frame) = event {
+//!             frames.push(frame);
+//!         }
+//!     }
+//!     while req.poll_event().is_some() {}
+//! }
+//! assert!(req.is_ready() && rep.is_ready());
+//! assert_eq!(frames, [Frame::Separator, Frame::Message("ping".into())]);
+//! ```
+use crate::errors::{ConnectionError, ParseError};
+use crate::packets::{null, Flags, FrameType, Greeting, Mechanism, RawFrame};
+use crate::sockets::SocketType;
+
+use bytes::{Buf, Bytes, BytesMut};
+use std::collections::VecDeque;
+
+/// What happened on a connection.
+#[derive(Debug)]
+pub enum Event {
+    /// The peer sent its greeting.
+    Greeting(Greeting),
+    /// The peers agreed on the security mechanism, and the local READY command is queued.
+    MechanismAgreed(Mechanism),
+    /// The peer sent the READY command of a compatible socket: messages can be exchanged.
+    Ready {
+        socket_type: SocketType,
+        identity: Option<Vec<u8>>,
+    },
+    /// A frame received once ready.
+    Frame(null::Frame),
+    /// The peer broke the protocol or rejected the handshake: nothing more is received.
+    ///
+    /// Handshakes rejected by the local peer are explained to the remote one with an ERROR
+    /// command, queued to be sent.
+    Error(crate::Error),
+}
+
+/// Size limits of the received frames.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct Limits {
+    /// Maximal size of any frame, commands included.
+    pub frame: Option<u64>,
+    /// Maximal size of all the frames of a message.
+    pub message: Option<u64>,
+}
+
+/// The bytes waiting to be sent.
+///
+/// Frame headers and bodies are queued as they are, so message bodies are never copied and can
+/// be written with vectored writes.
+#[derive(Debug, Default)]
+pub struct Transmit {
+    chunks: VecDeque<Bytes>,
+    /// Bytes left to send of each queued message.
+    messages: VecDeque<usize>,
+}
+
+impl Transmit {
+    /// Queue the frames of a message.
+    pub fn queue(&mut self, frames: impl IntoIterator<Item = null::Frame>) {
+        let mut len = 0;
+        for frame in frames {
+            let (header, body) = frame.to_parts();
+            len += header.len() + body.len();
+            self.chunks.push_back(header.into());
+            if !body.is_empty() {
+                self.chunks.push_back(body);
+            }
+        }
+        self.messages.push_back(len);
+    }
+
+    /// Queue raw bytes, counted as a message.
+    fn push(&mut self, bytes: Bytes) {
+        self.messages.push_back(bytes.len());
+        self.chunks.push_back(bytes);
+    }
+
+    /// Move the queue of `other` at the end of this one.
+    pub fn append(&mut self, other: &mut Transmit) {
+        self.chunks.append(&mut other.chunks);
+        self.messages.append(&mut other.messages);
+    }
+
+    /// Number of messages not fully sent yet.
+    pub fn queued(&self) -> usize {
+        self.messages.len()
+    }
+
+    /// Whether every queued byte was sent.
+    pub fn is_empty(&self) -> bool {
+        self.chunks.is_empty()
+    }
+
+    /// The bytes to send, in order.
+    pub fn chunks(&self) -> impl Iterator<Item = &Bytes> {
+        self.chunks.iter()
+    }
+
+    /// Forget the `sent` first bytes.
+    ///
+    /// # Panics
+    ///
+    /// If more bytes were sent than queued.
+    pub fn advance(&mut self, sent: usize) {
+        const PAST_THE_END: &str = "advanced past the queued bytes";
+        let mut left = sent;
+        while left > 0 {
+            let front = self.chunks.front_mut().expect(PAST_THE_END);
+            if left < front.len() {
+                front.advance(left);
+                left = 0;
+            } else {
+                left -= front.len();
+                self.chunks.pop_front();
+            }
+        }
+        let mut left = sent;
+        while left > 0 {
+            let front = self.messages.front_mut().expect(PAST_THE_END);
+            if left < *front {
+                *front -= left;
+                left = 0;
+            } else {
+                left -= *front;
+                self.messages.pop_front();
+            }
+        }
+    }
+
+    /// Take every byte to send at once, copying the message bodies.
+    pub fn take(&mut self) -> Vec<u8> {
+        self.messages.clear();
+        self.chunks.drain(..).fold(Vec::new(), |mut buf, chunk| {
+            buf.extend_from_slice(&chunk);
+            buf
+        })
+    }
+}
+
+/// The progress of a connection.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum State {
+    /// Waiting for the signature and the major version of the peer.
+    Signature,
+    /// Waiting for the rest of the peer greeting.
+    Greeting,
+    /// Waiting for the READY command of the peer.
+    Ready,
+    /// Exchanging messages.
+    Traffic,
+    /// Given up after an error.
+    Closed,
+}
+
+/// The protocol state of a connection of a `socket_type` socket, with the NULL mechanism.
+///
+/// Received bytes are given with [`receive`](Self::receive), and turned into events by
+/// [`poll_event`](Self::poll_event). The bytes to send are queued in the
+/// [`transmit`](Self::transmit) queue, from the start for the greeting.
+#[derive(Debug)]
+pub struct Connection {
+    socket_type: SocketType,
+    limits: Limits,
+    state: State,
+    greeting: Greeting,
+    peer_greeting: Option<Greeting>,
+    /// The received bytes not decoded yet.
+    received: BytesMut,
+    /// Size of the frames received so far of the message being received.
+    message_size: u64,
+    events: VecDeque<Event>,
+    transmit: Transmit,
+    /// The messages sent before the local READY command, queued after it.
+    early: Transmit,
+}
+
+impl Connection {
+    /// Length of the signature and the major version, sent before the rest of the greeting to
+    /// fail fast on peers which don't speak ZMTP 3.
+    const SIGNATURE_LEN: usize = 11;
+
+    pub fn new(socket_type: SocketType) -> Self {
+        Self {
+            socket_type,
+            limits: Limits::default(),
+            state: State::Signature,
+            greeting: Greeting::new(),
+            peer_greeting: None,
+            received: BytesMut::new(),
+            message_size: 0,
+            events: VecDeque::new(),
+            transmit: Transmit::default(),
+            early: Transmit::default(),
+        }
+        .with_greeting(Greeting::new())
+    }
+
+    /// Send `greeting` rather than the default one, advertising another security mechanism
+    /// and the role of the socket in it.
+    ///
+    /// Past the greeting, only the handshake of the NULL mechanism is implemented.
+    pub fn with_greeting(mut self, greeting: Greeting) -> Self {
+        self.transmit = Transmit::default();
+        self.transmit.push(Bytes::copy_from_slice(
+            &greeting.to_bytes()[..Self::SIGNATURE_LEN],
+        ));
+        self.greeting = greeting;
+        self
+    }
+
+    /// Fail on received frames larger than the `limits`.
+    pub fn with_limits(mut self, limits: Limits) -> Self {
+        self.limits = limits;
+        self
+    }
+
+    /// Whether the handshake is complete.
+    pub fn is_ready(&self) -> bool {
+        self.state == State::Traffic
+    }
+
+    /// Whether the connection was given up after an error.
+    pub fn is_closed(&self) -> bool {
+        self.state == State::Closed
+    }
+
+    /// The greeting the peer sent, once received.
+    pub fn peer_greeting(&self) -> Option<&Greeting> {
+        self.peer_greeting.as_ref()
+    }
+
+    /// Give bytes received from the peer. They are ignored once the connection is closed.
+    pub fn receive(&mut self, bytes: &[u8]) {
+        if !self.is_closed() {
+            self.received.extend_from_slice(bytes);
+        }
+    }
+
+    /// The buffer of the received bytes, to read them right into it rather than copying them
+    /// with [`receive`](Self::receive).
+    ///
+    /// Received frames are sliced out of this buffer, so their bodies are never copied. Bytes
+    /// must only be appended to it.
+    pub fn receive_buffer(&mut self) -> &mut BytesMut {
+        &mut self.received
+    }
+
+    /// The bytes to send to the peer.
+    pub fn transmit(&mut self) -> &mut Transmit {
+        &mut self.transmit
+    }
+
+    /// Queue the frames of a message, or a command, to be sent.
+    ///
+    /// Frames sent before the mechanism is agreed are held until the local READY command is
+    /// queued.
+    pub fn send(&mut self, frames: impl IntoIterator<Item = null::Frame>) {
+        match self.state {
+            State::Signature | State::Greeting => self.early.queue(frames),
+            _ => self.transmit.queue(frames),
+        }
+    }
+
+    /// The next event of the connection, if the bytes received so far make one.
+    pub fn poll_event(&mut self) -> Option<Event> {
+        loop {
+            if let Some(event) = self.events.pop_front() {
+                return Some(event);
+            }
+            let progressed = match self.state {
+                // received frames are most events, they aren't queued
+                State::Traffic => match self.decode() {
+                    Ok(frame) => return frame.map(Event::Frame),
+                    Err(err) => Err(err.into()),
+                },
+                _ => self.handshake().map_err(Into::into),
+            };
+            match progressed {
+                Ok(true) => (),
+                Ok(false) => return None,
+                Err(err) => {
+                    self.state = State::Closed;
+                    self.received = BytesMut::new();
+                    self.events.push_back(Event::Error(err));
+                }
+            }
+        }
+    }
+
+    /// Run the handshake as far as the received bytes allow, returning whether it progressed.
+    fn handshake(&mut self) -> Result<bool, ConnectionError> {
+        match self.state {
+            State::Signature => {
+                if self.received.first().is_some_and(|first| *first != 0xff) {
+                    return Err(ConnectionError::NotZmtp());
+                }
+                match self.received.get(..Self::SIGNATURE_LEN) {
+                    Some([.., last, major]) if last & 0x01 > 0 && *major >= 3 => (),
+                    Some(_) => return Err(ConnectionError::VersionMismatch()),
+                    None => return Ok(false),
+                }
+                let greeting = self.greeting.to_bytes();
+                let rest = Bytes::copy_from_slice(&greeting[Self::SIGNATURE_LEN..]);
+                self.transmit.push(rest);
+                self.state = State::Greeting;
+            }
+            State::Greeting => {
+                if self.received.len() < Greeting::LEN {
+                    return Ok(false);
+                }
+                let greeting = self.received.split_to(Greeting::LEN);
+                let greeting = Greeting::parse(&greeting)
+                    .map_err(|err| self.reject("invalid greeting", err.into()))?;
+                self.events.push_back(Event::Greeting(greeting.clone()));
+                let (mechanism, as_server) = (greeting.mechanism(), greeting.as_server());
+                self.peer_greeting = Some(greeting);
+                if mechanism != self.greeting.mechanism() {
+                    let err = ConnectionError::MechanismMismatch();
+                    return Err(self.reject("mechanism mismatch", err));
+                }
+                // NULL peers are symmetric, the other mechanisms need a client and a server
+                if mechanism != Mechanism::NULL && as_server == self.greeting.as_server() {
+                    let err = ConnectionError::RoleMismatch();
+                    return Err(self.reject("both peers claim the same role", err));
+                }
+                self.events.push_back(Event::MechanismAgreed(mechanism));
+                self.transmit.queue([null::Command::Ready {
+                    socket_type: Vec::from(self.socket_type.name()),
+                    identity: None,
+                }
+                .into()]);
+                self.transmit.append(&mut self.early);
+                self.state = State::Ready;
+            }
+            State::Ready => {
+                let Some(frame) = self.decode()? else {
+                    return Ok(false);
+                };
+                match frame {
+                    null::Frame::Command(null::Command::Ready {
+                        socket_type,
+                        identity,
+                    }) => {
+                        let peer = SocketType::from_name(&socket_type)
+                            .filter(|peer| self.socket_type.is_compatible(*peer));
+                        let Some(socket_type) = peer else {
+                            let name = String::from_utf8_lossy(&socket_type).into_owned();
+                            let err = ConnectionError::SocketTypeMismatch(name);
+                            return Err(self.reject("incompatible socket type", err));
+                        };
+                        self.events.push_back(Event::Ready {
+                            socket_type,
+                            identity,
+                        });
+                        self.state = State::Traffic;
+                    }
+                    null::Frame::Command(null::Command::Error(reason)) => {
+                        return Err(ConnectionError::PeerRejected(reason))
+                    }
+                    _ => {
+                        let err = ParseError::UnexpectedFrame("a READY command");
+                        return Err(err.into());
+                    }
+                }
+            }
+            State::Traffic | State::Closed => return Ok(false),
+        }
+        Ok(true)
+    }
+
+    /// Tell the peer why its handshake is rejected with an ERROR command, returning `err`.
+    fn reject(&mut self, reason: &str, err: ConnectionError) -> ConnectionError {
+        let error = null::Command::Error(reason.to_string());
+        self.transmit.queue([error.into()]);
+        err
+    }
+
+    /// Decode the next received frame, checking its size from its header before it is
+    /// buffered whole.
+    fn decode(&mut self) -> Result<Option<null::Frame>, ParseError> {
+        if let Some((header, _)) = FrameType::parse(&self.received)? {
+            self.check(header.flags, header.size)?;
+        }
+        let Some(raw_frame) = RawFrame::decode(&mut self.received)? else {
+            return Ok(None);
+        };
+        match &raw_frame {
+            RawFrame::More(body) => self.message_size += body.len() as u64,
+            RawFrame::Message(_) => self.message_size = 0,
+            RawFrame::Command(_) => (),
+        }
+        raw_frame.try_into().map(Some)
+    }
+
+    /// Check the size announced by a frame header.
+    fn check(&self, flags: Flags, size: u64) -> Result<(), ParseError> {
+        if let Some(max) = self.limits.frame.filter(|max| size > *max) {
+            return Err(ParseError::FrameTooLarge(size, max));
+        }
+        let total = self.message_size.saturating_add(size);
+        match self.limits.message {
+            Some(max) if flags.is_message() && total > max => {
+                Err(ParseError::MessageTooLarge(total, max))
+            }
+            _ => Ok(()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Connection, Event, Limits, Transmit};
+    use crate::errors::{ConnectionError, ParseError};
+    use crate::packets::null::{Command, Frame};
+    use crate::packets::{Greeting, Mechanism, RawFrame};
+    use crate::sockets::SocketType;
+    use crate::Error;
+    use bytes::{Bytes, BytesMut};
+
+    fn events(connection: &mut Connection) -> Vec<Event> {
+        std::iter::from_fn(|| connection.poll_event()).collect()
+    }
+
+    /// Pass the bytes of each connection to the other until they have nothing left to say,
+    /// returning their events and the bytes `a` sent.
+    fn exchange(a: &mut Connection, b: &mut Connection) -> (Vec<Event>, Vec<Event>, Vec<u8>) {
+        let (mut a_events, mut b_events, mut sent) = (Vec::new(), Vec::new(), Vec::new());
+        while !(a.transmit().is_empty() && b.transmit().is_empty()) {
+            let bytes = a.transmit().take();
+            b.receive(&bytes);
+            sent.extend(bytes);
+            a.receive(&b.transmit().take());
+            a_events.extend(events(a));
+            b_events.extend(events(b));
+        }
+        (a_events, b_events, sent)
+    }
+
+    /// The bytes of a peer sending `greeting` then `frames`.
+    fn peer(greeting: Greeting, frames: Vec<Frame>) -> Vec<u8> {
+        let mut bytes = greeting.to_bytes().to_vec();
+        frames
+            .iter()
+            .for_each(|frame| bytes.extend(frame.to_vec_u8()));
+        bytes
+    }
+
+    fn ready(socket_type: SocketType) -> Frame {
+        Frame::Command(Command::Ready {
+            socket_type: socket_type.name().into(),
+            identity: None,
+        })
+    }
+
+    /// The frames queued after the greeting.
+    fn sent_frames(connection: &mut Connection) -> Vec<Frame> {
+        let mut bytes = BytesMut::from(&connection.transmit().take()[Greeting::LEN..]);
+        std::iter::from_fn(|| RawFrame::decode(&mut bytes).unwrap())
+            .map(|raw| Frame::try_from(raw).unwrap())
+            .collect()
+    }
+
+    fn rejected(reason: &str) -> Frame {
+        Frame::Command(Command::Error(reason.into()))
+    }
+
+    #[test]
+    fn handshake() {
+        let mut req = Connection::new(SocketType::Req);
+        let mut rep = Connection::new(SocketType::Rep);
+        // sent before the handshake, held until the READY command
+        req.send([Frame::Separator, Frame::Message("ping".into())]);
+        assert_eq!(req.transmit().queued(), 1);
+        assert!(!req.is_ready());
+
+        let (req_events, rep_events, _) = exchange(&mut req, &mut rep);
+        assert!(req.is_ready() && rep.is_ready());
+        assert_eq!(req.peer_greeting(), Some(&Greeting::new()));
+        assert!(matches!(
+            &req_events[..],
+            [
+                Event::Greeting(_),
+                Event::MechanismAgreed(Mechanism::NULL),
+                Event::Ready {
+                    socket_type: SocketType::Rep,
+                    identity: None
+                },
+            ]
+        ));
+        assert!(matches!(
+            &rep_events[..],
+            [
+                Event::Greeting(_),
+                Event::MechanismAgreed(Mechanism::NULL),
+                Event::Ready {
+                    socket_type: SocketType::Req,
+                    ..
+                },
+                Event::Frame(Frame::Separator),
+                Event::Frame(Frame::Message(ping)),
+            ] if ping == "ping"
+        ));
+
+        rep.send([Frame::Separator, Frame::More("a".into()), "b".into()]);
+        let (req_events, _, _) = exchange(&mut req, &mut rep);
+        let frames: Vec<_> = req_events
+            .into_iter()
+            .map(|event| match event {
+                Event::Frame(frame) => frame,
+                event => panic!("unexpected {event:?}"),
+            })
+            .collect();
+        assert_eq!(
+            frames,
+            [Frame::Separator, Frame::More("a".into()), "b".into()]
+        );
+    }
+
+    #[test]
+    fn byte_by_byte() {
+        let mut dealer = Connection::new(SocketType::Dealer);
+        let mut router = Connection::new(SocketType::Router);
+        dealer.send([Frame::Message(Bytes::from(vec![7; 300]))]);
+        let (_, _, sent) = exchange(&mut dealer, &mut router);
+
+        let mut router = Connection::new(SocketType::Router);
+        let mut received = Vec::new();
+        for byte in sent {
+            router.receive(&[byte]);
+            received.extend(events(&mut router));
+        }
+        assert!(matches!(
+            &received[..],
+            [
+                Event::Greeting(_),
+                Event::MechanismAgreed(_),
+                Event::Ready { .. },
+                Event::Frame(Frame::Message(body)),
+            ] if body[..] == [7; 300]
+        ));
+    }
+
+    #[test]
+    fn not_zmtp() {
+        let mut rep = Connection::new(SocketType::Rep);
+        rep.receive(b"G");
+        assert!(matches!(
+            &events(&mut rep)[..],
+            [Event::Error(Error::Connection(ConnectionError::NotZmtp()))]
+        ));
+        assert!(rep.is_closed());
+        // only the signature was sent
+        assert_eq!(rep.transmit().take().len(), 11);
+        rep.receive(b"ET / HTTP/1.1");
+        assert!(rep.poll_event().is_none());
+    }
+
+    #[test]
+    fn old_version() {
+        let mut rep = Connection::new(SocketType::Rep);
+        let mut greeting = Greeting::new().to_bytes();
+        greeting[10] = 2;
+        rep.receive(&greeting[..10]);
+        assert!(rep.poll_event().is_none());
+        rep.receive(&greeting[10..]);
+        assert!(matches!(
+            &events(&mut rep)[..],
+            [Event::Error(Error::Connection(
+                ConnectionError::VersionMismatch()
+            ))]
+        ));
+        assert_eq!(rep.transmit().take().len(), 11);
+    }
+
+    #[test]
+    fn invalid_greeting() {
+        let mut rep = Connection::new(SocketType::Rep);
+        let mut greeting = Greeting::new().to_bytes();
+        greeting[40] = 1;
+        rep.receive(&greeting);
+        assert!(matches!(
+            &events(&mut rep)[..],
+            [Event::Error(Error::Connection(
+                ConnectionError::InvalidHandshake(ParseError::InvalidGreeting("filler"))
+            ))]
+        ));
+        assert_eq!(sent_frames(&mut rep), [rejected("invalid greeting")]);
+    }
+
+    #[test]
+    fn mechanism_mismatch() {
+        let mut curve = [0; 20];
+        curve[..5].copy_from_slice(b"CURVE");
+        let greeting = Greeting::new().with_mechanism(Mechanism(curve));
+        let mut rep = Connection::new(SocketType::Rep);
+        rep.receive(&peer(greeting, vec![ready(SocketType::Req)]));
+        assert!(matches!(
+            &events(&mut rep)[..],
+            [
+                Event::Greeting(_),
+                Event::Error(Error::Connection(ConnectionError::MechanismMismatch())),
+            ]
+        ));
+        assert_eq!(sent_frames(&mut rep), [rejected("mechanism mismatch")]);
+    }
+
+    #[test]
+    fn role_mismatch() {
+        let mut plain = [0; 20];
+        plain[..5].copy_from_slice(b"PLAIN");
+        let server = Greeting::new()
+            .with_mechanism(Mechanism(plain))
+            .with_as_server(true);
+        let mut rep = Connection::new(SocketType::Rep).with_greeting(server.clone());
+        rep.receive(&peer(server, vec![ready(SocketType::Req)]));
+        assert!(matches!(
+            &events(&mut rep)[..],
+            [
+                Event::Greeting(_),
+                Event::Error(Error::Connection(ConnectionError::RoleMismatch())),
+            ]
+        ));
+        assert_eq!(
+            sent_frames(&mut rep),
+            [rejected("both peers claim the same role")]
+        );
+    }
+
+    #[test]
+    fn incompatible_socket_type() {
+        let mut req = Connection::new(SocketType::Req);
+        let mut publisher = Connection::new(SocketType::Pub);
+        let (req_events, _, _) = exchange(&mut req, &mut publisher);
+        assert!(matches!(
+            &req_events[..],
+            [
+                Event::Greeting(_),
+                Event::MechanismAgreed(_),
+                Event::Error(Error::Connection(ConnectionError::SocketTypeMismatch(name))),
+            ] if name == "PUB"
+        ));
+        assert!(req.is_closed() && publisher.is_closed());
+
+        let mut rep = Connection::new(SocketType::Rep);
+        let unknown = Frame::Command(Command::Ready {
+            socket_type: b"HTTP".to_vec(),
+            identity: None,
+        });
+        rep.receive(&peer(Greeting::new(), vec![unknown]));
+        assert!(matches!(
+            events(&mut rep).pop(),
+            Some(Event::Error(Error::Connection(ConnectionError::SocketTypeMismatch(name))))
+                if name == "HTTP"
+        ));
+        assert_eq!(
+            sent_frames(&mut rep),
+            [ready(SocketType::Rep), rejected("incompatible socket type")]
+        );
+    }
+
+    #[test]
+    fn peer_rejected() {
+        let mut req = Connection::new(SocketType::Req);
+        req.receive(&peer(Greeting::new(), vec![rejected("go away")]));
+        assert!(matches!(
+            events(&mut req).pop(),
+            Some(Event::Error(Error::Connection(ConnectionError::PeerRejected(reason))))
+                if reason == "go away"
+        ));
+        // the peer rejected the handshake first, it isn't told why it's given up
+        assert_eq!(sent_frames(&mut req), [ready(SocketType::Req)]);
+    }
+
+    #[test]
+    fn message_before_ready() {
+        let mut rep = Connection::new(SocketType::Rep);
+        rep.receive(&peer(Greeting::new(), vec!["early".into()]));
+        assert!(matches!(
+            events(&mut rep).pop(),
+            Some(Event::Error(Error::Connection(
+                ConnectionError::InvalidHandshake(ParseError::UnexpectedFrame(_))
+            )))
+        ));
+
+        let mut rep = Connection::new(SocketType::Rep);
+        rep.receive(&peer(Greeting::new(), vec![]));
+        // a command name longer than the command
+        rep.receive(&[0x04, 1, 5]);
+        assert!(matches!(
+            events(&mut rep).pop(),
+            Some(Event::Error(Error::Connection(
+                ConnectionError::InvalidHandshake(ParseError::Truncated(_))
+            )))
+        ));
+    }
+
+    #[test]
+    fn limits() {
+        let limits = Limits {
+            frame: Some(30),
+            message: Some(12),
+        };
+        let mut pull = Connection::new(SocketType::Pull).with_limits(limits);
+        pull.receive(&peer(Greeting::new(), vec![ready(SocketType::Push)]));
+        assert_eq!(events(&mut pull).len(), 3);
+        assert!(pull.is_ready());
+        // the header is enough to fail, the body is not waited for
+        pull.receive(&[0x00, 40]);
+        assert!(matches!(
+            &events(&mut pull)[..],
+            [Event::Error(Error::Parse(ParseError::FrameTooLarge(
+                40, 30
+            )))]
+        ));
+        assert!(pull.is_closed());
+
+        let mut pull = Connection::new(SocketType::Pull).with_limits(limits);
+        let frames = vec![
+            ready(SocketType::Push),
+            Frame::More(Bytes::from(vec![0; 8])),
+            Frame::Message(Bytes::from(vec![0; 4])),
+            Frame::More(Bytes::from(vec![0; 8])),
+            Frame::Message(Bytes::from(vec![0; 8])),
+        ];
+        pull.receive(&peer(Greeting::new(), frames));
+        assert!(matches!(
+            &events(&mut pull)[3..],
+            [
+                Event::Frame(Frame::More(_)),
+                Event::Frame(Frame::Message(_)),
+                Event::Frame(Frame::More(_)),
+                Event::Error(Error::Parse(ParseError::MessageTooLarge(16, 12))),
+            ]
+        ));
+    }
+
+    #[test]
+    fn transmit() {
+        let mut transmit = Transmit::default();
+        assert!(transmit.is_empty());
+        transmit.queue([Frame::More("abc".into()), Frame::Message("de".into())]);
+        transmit.queue([Frame::Message("".into())]);
+        // two frames of a 2 bytes header, and an empty one
+        assert_eq!(transmit.chunks().count(), 5);
+        assert_eq!(transmit.queued(), 2);
+
+        transmit.advance(4);
+        assert_eq!(transmit.chunks().next().unwrap(), &Bytes::from("c"));
+        transmit.advance(5);
+        assert_eq!(transmit.queued(), 1);
+
+        let mut other = Transmit::default();
+        other.queue([Frame::Message("f".into())]);
+        transmit.append(&mut other);
+        assert!(other.is_empty());
+        assert_eq!(transmit.queued(), 2);
+        assert_eq!(transmit.take(), [0, 0, 0, 1, b'f']);
+        assert_eq!(transmit.queued(), 0);
+    }
+
+    #[test]
+    #[should_panic(expected = "advanced past the queued bytes")]
+    fn transmit_past_the_end() {
+        let mut transmit = Transmit::default();
+        transmit.queue([Frame::Message("abc".into())]);
+        transmit.advance(6);
+    }
+}
@@ -1,8 +1,10 @@
 //! ZMTP errors
+use crate::sockets::{RoutingId, SocketType, ZmtpReceiver, ZmtpSender};
+use std::sync::Arc;
 use thiserror::Error;
 
 /// These are the errors you may encounter using ZMTP.
-#[derive(Error, Debug)]
+#[derive(Error, Debug, Clone)]
 pub enum Error {
     /// Network or socket connection errors
     #[error("Connection error, {0}")]
@@ -10,14 +12,29 @@ pub enum Error {
     /// An error parsing a packet
     #[error("Parse error, {0}")]
     Parse(#[from] ParseError),
+    /// The socket can't send the message
+    #[error("Socket error, {0}")]
+    Socket(#[from] SocketError),
 }
 
 /// Internal connection error.
-#[derive(Error, Debug)]
+#[derive(Error, Debug, Clone)]
 pub enum ConnectionError {
     /// Couldn't connect to the (host, port)
     #[error("cann't connect to tcp://{0}:{1}")]
     UnaccessibleHost(String, u16),
+    /// Couldn't listen on the (host, port)
+    #[error("cann't bind tcp://{0}:{1}")]
+    UnbindableHost(String, u16),
+    /// Couldn't establish the TCP connection to (host, port) in time.
+    #[error("timed out connecting to tcp://{0}:{1}")]
+    ConnectTimeout(String, u16),
+    /// The remote didn't complete the ZMTP handshake in time.
+    #[error("handshake timed out")]
+    HandshakeTimeout(),
+    /// The remote doesn't start its greeting with the ZMTP signature.
+    #[error("remote is not a ZMTP peer")]
+    NotZmtp(),
     /// The remote ZMTP version is not compatible with the local version.
     /// As specified by ZMTP protocol, this could appen only when the remote is of a lower version.
     /// Right now, this crate does not provide any back compatibility mechanism.
@@ -27,10 +44,99 @@ pub enum ConnectionError {
     /// Right now, this crate provide only NULL auth mechanism.
     #[error("remote authentification mechanism incompatibility")]
     MechanismMismatch(),
+    /// Both peers claim the same role of the security mechanism.
+    #[error("both peers claim the same security role")]
+    RoleMismatch(),
+    /// The remote rejected the handshake with an ERROR command.
+    #[error("rejected by the remote, {0}")]
+    PeerRejected(String),
+    /// The remote socket type can't talk with the local one.
+    #[error("incompatible remote socket type {0}")]
+    SocketTypeMismatch(String),
+    /// The remote didn't follow the handshake.
+    #[error("invalid handshake, {0}")]
+    InvalidHandshake(#[from] ParseError),
     /// Socket IO error.
     #[error("I/O {0}")]
-    IOError(#[from] std::io::Error),
+    IOError(#[source] Arc<std::io::Error>),
 }
 
-#[derive(Error, Debug)]
-pub enum ParseError {}
+impl From<std::io::Error> for ConnectionError {
+    fn from(err: std::io::Error) -> Self {
+        Self::IOError(Arc::new(err))
+    }
+}
+
+/// A received frame does not follow the ZMTP grammar.
+#[derive(Error, Debug, Clone)]
+pub enum ParseError {
+    /// The frame ends before the field is complete.
+    #[error("truncated {0}")]
+    Truncated(&'static str),
+    /// The command name is not part of the supported protocol.
+    #[error("{0} not supported by this protocol")]
+    UnknownCommand(String),
+    /// A command lacks a mandatory metadata property.
+    #[error("missing {0} property")]
+    MissingProperty(&'static str),
+    /// A greeting field is not allowed by the spec.
+    #[error("invalid greeting {0}")]
+    InvalidGreeting(&'static str),
+    /// The frame flags are not allowed by the spec.
+    #[error("invalid frame flags {0:#04x}")]
+    InvalidFlags(u8),
+    /// A frame is larger than the maximal frame size: (size, maximum).
+    #[error("frame of {0} bytes exceeds the {1} bytes limit")]
+    FrameTooLarge(u64, u64),
+    /// A message is larger than the maximal message size: (size so far, maximum).
+    #[error("message of {0} bytes exceeds the {1} bytes limit")]
+    MessageTooLarge(u64, u64),
+    /// The frame is not the one expected at this point of the protocol.
+    #[error("unexpected frame, expected {0}")]
+    UnexpectedFrame(&'static str),
+    /// A message doesn't have the number of frames of its type: (expected, received).
+    #[error("expected a message of {0} frames, received {1}")]
+    UnexpectedFrameCount(usize, usize),
+    /// A message body doesn't decode to the expected type.
+    #[error("invalid message body, {0}")]
+    InvalidBody(String),
+}
+
+/// A message the socket can't send.
+#[derive(Error, Debug, Clone)]
+pub enum SocketError {
+    /// The socket type only sends single-part messages.
+    #[error("{0} sockets only send single-part messages")]
+    MultipartNotAllowed(SocketType),
+    /// The group name is longer than [`MAX_GROUP_LEN`](crate::sockets::MAX_GROUP_LEN).
+    #[error("group {0:?} is too long")]
+    GroupTooLong(String),
+    /// The socket type talks with a single peer, and already has one.
+    #[error("{0} socket already has a peer")]
+    AlreadyPaired(SocketType),
+    /// No connected peer has the routing id.
+    #[error("no peer with routing id {0}")]
+    UnknownRoutingId(RoutingId),
+    /// A REP socket replies only once to each received request.
+    #[error("no request to reply to")]
+    NoRequest,
+    /// A blocking socket didn't send or receive the message in time.
+    #[error("the operation timed out")]
+    TimedOut,
+    /// The value can't be encoded into a message body.
+    #[error("can't encode the message body, {0}")]
+    InvalidBody(String),
+}
+
+/// Returned by [`ZmtpSender::reunite`] when the halves don't come from the same socket.
+///
+/// Give the halves back.
+#[derive(Error)]
+#[error("tried to reunite halves of different sockets")]
+pub struct ReuniteError(pub ZmtpSender, pub ZmtpReceiver);
+
+impl std::fmt::Debug for ReuniteError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str("ReuniteError(..)")
+    }
+}
@@ -1,8 +1,17 @@
+#![forbid(unsafe_code)]
+#[cfg(feature = "blocking")]
+pub mod blocking;
+pub mod connection;
 pub mod errors;
 pub use errors::Error;
 
+pub mod message;
+pub use message::Multipart;
+
 pub mod packets;
+mod runtime;
 pub mod sockets;
+pub mod typed;
 
 /// Returned by every ZMTP's function which may fail.
 pub type Result<T> = core::result::Result<T, errors::Error>;
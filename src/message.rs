@@ -0,0 +1,86 @@
+//! ZMTP messages
+use crate::packets::null;
+use bytes::Bytes;
+
+/// A message made of one or more frames.
+///
+/// Each part is sent as a frame flagged with MORE, except the last one.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Multipart(pub Vec<Bytes>);
+
+impl Multipart {
+    /// The frames carrying this message over the wire.
+    ///
+    /// An empty multipart is sent as a single empty frame.
+    pub fn into_frames(self) -> impl Iterator<Item = null::Frame> {
+        let mut parts = self.0;
+        let last = parts.pop().unwrap_or_default();
+        parts
+            .into_iter()
+            .map(null::Frame::More)
+            .chain(core::iter::once(null::Frame::Message(last)))
+    }
+
+    /// The body of a single-part message, or `None` if it has several parts.
+    ///
+    /// An empty multipart is a single empty part, as on the wire.
+    pub fn into_single(self) -> Option<Bytes> {
+        let mut parts = self.0;
+        match parts.len() {
+            0 | 1 => Some(parts.pop().unwrap_or_default()),
+            _ => None,
+        }
+    }
+}
+
+impl From<Vec<Bytes>> for Multipart {
+    fn from(parts: Vec<Bytes>) -> Self {
+        Self(parts)
+    }
+}
+
+impl From<Bytes> for Multipart {
+    fn from(part: Bytes) -> Self {
+        Self(vec![part])
+    }
+}
+
+impl From<Multipart> for Vec<Bytes> {
+    fn from(msg: Multipart) -> Self {
+        msg.0
+    }
+}
+
+impl FromIterator<Bytes> for Multipart {
+    fn from_iter<I: IntoIterator<Item = Bytes>>(iter: I) -> Self {
+        Self(iter.into_iter().collect())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Multipart;
+    use crate::packets::null::Frame;
+    use bytes::Bytes;
+
+    #[test]
+    fn more_flags() {
+        let msg = Multipart(vec![
+            Bytes::from("topic"),
+            Bytes::new(),
+            Bytes::from("body"),
+        ]);
+        assert_eq!(
+            msg.into_frames().collect::<Vec<_>>(),
+            vec![
+                Frame::More("topic".into()),
+                Frame::More(Bytes::new()),
+                Frame::Message("body".into()),
+            ]
+        );
+        assert_eq!(
+            Multipart::default().into_frames().collect::<Vec<_>>(),
+            vec![Frame::Message(Bytes::new())]
+        );
+    }
+}
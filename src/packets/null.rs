@@ -1,4 +1,6 @@
 use super::zmtp::RawFrame;
+use crate::errors::ParseError;
+use bytes::Bytes;
 
 #[derive(Debug, PartialEq, Eq)]
 pub enum Command {
@@ -7,102 +9,76 @@ pub enum Command {
         socket_type: Vec<u8>,
         identity: Option<Vec<u8>>,
     },
+    /// A DISH socket joins a group.
+    Join(Vec<u8>),
+    /// A DISH socket leaves a group.
+    Leave(Vec<u8>),
 }
 
 #[derive(Debug, PartialEq, Eq)]
 pub enum Frame {
     Command(Command),
-    Message(Vec<u8>),
+    /// The last frame of a message.
+    Message(Bytes),
+    /// A frame followed by other frames of the same message.
+    More(Bytes),
+    /// An empty frame followed by other frames, delimiting the message envelope.
     Separator,
 }
 
 impl Frame {
+    /// Encode the frame header and return it along with the frame body.
+    ///
+    /// The body of a message is shared, not copied, so both parts can be written
+    /// with a single vectored write.
+    pub fn to_parts(&self) -> (Vec<u8>, Bytes) {
+        use super::{Flags, FrameType};
+        let (flags, body) = match self {
+            Frame::Command(f) => (Flags::default().command(), Bytes::from(f.to_vec_u8())),
+            Frame::Message(f) => (Flags::default().message(), f.clone()),
+            Frame::More(f) => (Flags::default().more(), f.clone()),
+            Frame::Separator => (Flags::default().more(), Bytes::new()),
+        };
+        (FrameType::new(flags, body.len() as u64).to_bytes(), body)
+    }
+
     pub fn to_vec_u8(&self) -> Vec<u8> {
-        use super::{Flags, FrameType, Packet};
-        match self {
-            Frame::Command(f) => {
-                let f_data = f.to_vec_u8();
-                let f_len = f_data.len();
-                let flags = Flags::default().command();
-                let mut buf = if f_len < 256 {
-                    Vec::from(
-                        FrameType {
-                            flags,
-                            size: f_len as u8,
-                        }
-                        .as_bytes(),
-                    )
-                } else {
-                    let flags = flags.big();
-                    Vec::from(
-                        FrameType {
-                            flags,
-                            size: f_len as u64,
-                        }
-                        .as_bytes(),
-                    )
-                };
-                buf.extend(f_data);
-                buf
-            }
-            Frame::Message(f) => {
-                let f_data = f;
-                let f_len = f_data.len();
-                let flags = Flags::default().message();
-                let mut buf = if f_len < 256 {
-                    Vec::from(
-                        FrameType {
-                            flags,
-                            size: f_len as u8,
-                        }
-                        .as_bytes(),
-                    )
-                } else {
-                    let flags = flags.big();
-                    Vec::from(
-                        FrameType {
-                            flags,
-                            size: f_len as u64,
-                        }
-                        .as_bytes(),
-                    )
-                };
-                buf.extend(f_data);
-                buf
-            }
-            Frame::Separator => {
-                let flags = Flags::default().more();
-                Vec::from(FrameType { flags, size: 0u8 }.as_bytes())
-            }
-        }
+        let (mut buf, body) = self.to_parts();
+        buf.extend(body);
+        buf
     }
 }
 
 impl TryFrom<RawFrame> for Frame {
-    type Error = ();
-    fn try_from(f: RawFrame) -> Result<Frame, ()> {
+    type Error = ParseError;
+    fn try_from(f: RawFrame) -> Result<Frame, ParseError> {
         Ok(match f {
             RawFrame::Command(ref arr) => {
-                let size = arr[0] as usize;
-                let tail = &arr[1..];
-                let command = match &tail[..size] {
+                let bytes = |from: usize, len: usize| {
+                    arr.get(from..from + len)
+                        .ok_or(ParseError::Truncated("command"))
+                };
+                let size = *bytes(0, 1)?.first().unwrap() as usize;
+                let command = match bytes(1, size)? {
                     br#"ERROR"# => {
-                        Command::Error(String::from_utf8(tail[size + 1..].into()).unwrap())
+                        let reason_size = bytes(1 + size, 1)?[0] as usize;
+                        let reason = bytes(2 + size, reason_size)?;
+                        Command::Error(String::from_utf8_lossy(reason).into_owned())
                     }
                     br#"READY"# => {
                         let mut socket_type = None;
                         let mut identity = None;
-                        let mut pos = size;
-                        while pos < tail.len() {
-                            let size = tail[pos] as usize;
+                        let mut pos = 1 + size;
+                        while pos < arr.len() {
+                            let size = bytes(pos, 1)?[0] as usize;
                             pos += 1;
-                            let key = &tail[pos..pos + size];
+                            let key = bytes(pos, size)?;
                             pos += size;
                             let mut raw_size: [u8; 4] = [0u8; 4];
-                            raw_size[..4].copy_from_slice(&tail[pos..pos + 4]);
+                            raw_size.copy_from_slice(bytes(pos, 4)?);
                             let size = u32::from_be_bytes(raw_size) as usize;
                             pos += 4;
-                            let val = Vec::from(&tail[pos..pos + size]);
+                            let val = Vec::from(bytes(pos, size)?);
                             pos += size;
                             match key {
                                 br#"Socket-Type"# => socket_type = Some(val),
@@ -110,26 +86,31 @@ impl TryFrom<RawFrame> for Frame {
                                 _ => (),
                             }
                         }
-                        let socket_type = socket_type.unwrap();
+                        let socket_type =
+                            socket_type.ok_or(ParseError::MissingProperty("Socket-Type"))?;
                         Command::Ready {
                             socket_type,
                             identity,
                         }
                     }
-                    cmd => panic!(
-                        "{} not supported by this protocol",
-                        String::from_utf8(cmd.into()).unwrap()
-                    ),
+                    br#"JOIN"# => Command::Join(Vec::from(&arr[1 + size..])),
+                    br#"LEAVE"# => Command::Leave(Vec::from(&arr[1 + size..])),
+                    cmd => {
+                        return Err(ParseError::UnknownCommand(
+                            String::from_utf8_lossy(cmd).into_owned(),
+                        ))
+                    }
                 };
                 Frame::Command(command)
             }
-            RawFrame::Message(msg) => {
+            RawFrame::More(msg) => {
                 if msg.is_empty() {
                     Frame::Separator
                 } else {
-                    Frame::Message(msg)
+                    Frame::More(msg)
                 }
             }
+            RawFrame::Message(msg) => Frame::Message(msg),
         })
     }
 }
@@ -159,9 +140,33 @@ impl Command {
                 }
                 buf
             }
-            Command::Error(_msg) => todo!(),
+            Command::Error(msg) => {
+                let key = br#"ERROR"#;
+                // the reason is a short string, truncated on a char boundary
+                let mut len = msg.len().min(u8::MAX as usize);
+                while !msg.is_char_boundary(len) {
+                    len -= 1;
+                }
+                let mut buf = Vec::new();
+                buf.push(key.len() as u8);
+                buf.extend(key);
+                buf.push(len as u8);
+                buf.extend(&msg.as_bytes()[..len]);
+                buf
+            }
+            Command::Join(group) => Self::group_command(br#"JOIN"#, group),
+            Command::Leave(group) => Self::group_command(br#"LEAVE"#, group),
         }
     }
+
+    /// A command whose body is the group name.
+    fn group_command(key: &[u8], group: &[u8]) -> Vec<u8> {
+        let mut buf = Vec::new();
+        buf.push(key.len() as u8);
+        buf.extend(key);
+        buf.extend(group);
+        buf
+    }
 }
 
 impl From<Command> for Frame {
@@ -172,7 +177,7 @@ impl From<Command> for Frame {
 
 impl<Message> From<Message> for Frame
 where
-    Message: Into<Vec<u8>>,
+    Message: Into<Bytes>,
 {
     fn from(msg: Message) -> Self {
         Frame::Message(msg.into())
@@ -182,6 +187,7 @@ where
 #[cfg(test)]
 mod tests {
     use super::{Command, Frame};
+    use crate::errors::ParseError;
     use crate::packets;
 
     #[test]
@@ -191,8 +197,74 @@ mod tests {
             identity: Some(Vec::from(&b"test.identity"[..])),
         };
         assert_eq!(
-            Frame::try_from(packets::RawFrame::Command(cmd.to_vec_u8())),
-            Ok(Frame::Command(cmd))
+            Frame::try_from(packets::RawFrame::Command(cmd.to_vec_u8().into())).unwrap(),
+            Frame::Command(cmd)
+        );
+        for cmd in [
+            Command::Join(Vec::from(&b"weather"[..])),
+            Command::Leave(Vec::new()),
+        ] {
+            assert_eq!(
+                Frame::try_from(packets::RawFrame::Command(cmd.to_vec_u8().into())).unwrap(),
+                Frame::Command(cmd)
+            );
+        }
+    }
+
+    #[test]
+    fn error_reason() {
+        let cmd = Command::Error("invalid socket type".to_string());
+        assert_eq!(
+            Frame::try_from(packets::RawFrame::Command(cmd.to_vec_u8().into())).unwrap(),
+            Frame::Command(cmd)
         );
+        let long = Command::Error("é".repeat(200));
+        let Frame::Command(Command::Error(reason)) =
+            Frame::try_from(packets::RawFrame::Command(long.to_vec_u8().into())).unwrap()
+        else {
+            panic!("not an ERROR command");
+        };
+        assert_eq!(reason, "é".repeat(127));
+    }
+
+    #[test]
+    fn malformed_command() {
+        let cmd = Command::Ready {
+            socket_type: Vec::from(&b"REQ"[..]),
+            identity: None,
+        }
+        .to_vec_u8();
+        for len in 0..cmd.len() {
+            let raw = packets::RawFrame::Command(cmd[..len].to_vec().into());
+            assert!(Frame::try_from(raw).is_err());
+        }
+        let raw = packets::RawFrame::Command(b"\x04PING"[..].into());
+        assert!(matches!(
+            Frame::try_from(raw),
+            Err(ParseError::UnknownCommand(cmd)) if cmd == "PING"
+        ));
+    }
+
+    #[test]
+    fn zero_copy_decode() {
+        let frames = [
+            Frame::More(vec![42u8; 300].into()),
+            Frame::Separator,
+            Frame::Message("small"[..].into()),
+            Frame::Message(bytes::Bytes::new()),
+        ];
+        let mut buf = bytes::BytesMut::new();
+        for f in &frames {
+            buf.extend(f.to_vec_u8());
+        }
+        let shared = buf.as_ptr() as usize..buf.as_ptr() as usize + buf.len();
+        for f in frames {
+            let raw = packets::RawFrame::decode(&mut buf).unwrap().unwrap();
+            if let packets::RawFrame::Message(body) | packets::RawFrame::More(body) = &raw {
+                assert!(body.is_empty() || shared.contains(&(body.as_ptr() as usize)));
+            }
+            assert_eq!(Frame::try_from(raw).unwrap(), f);
+        }
+        assert!(packets::RawFrame::decode(&mut buf).unwrap().is_none());
     }
 }
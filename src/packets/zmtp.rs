@@ -1,7 +1,8 @@
-use bytes::Bytes;
+use crate::errors::ParseError;
+use bytes::{Bytes, BytesMut};
 
-#[repr(C, packed)]
-#[derive(Debug)]
+/// The 64 bytes greeting each peer sends first.
+#[derive(Debug, Clone, PartialEq, Eq)]
 pub struct Greeting {
     signature: [u8; 10],
     version: Version,
@@ -10,13 +11,25 @@ pub struct Greeting {
     filler: [u8; 31],
 }
 
-#[repr(C, packed)]
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub struct Version {
     pub major: u8,
     pub minor: u8,
 }
 
+impl Version {
+    pub fn to_bytes(&self) -> [u8; 2] {
+        [self.major, self.minor]
+    }
+
+    pub fn from_bytes(buf: [u8; 2]) -> Self {
+        Self {
+            major: buf[0],
+            minor: buf[1],
+        }
+    }
+}
+
 const fn zerro_padded<const M: usize, const N: usize>(arr: &[u8; M]) -> [u8; N] {
     let mut ret = [0u8; N];
     let mut i = 0;
@@ -27,68 +40,140 @@ const fn zerro_padded<const M: usize, const N: usize>(arr: &[u8; M]) -> [u8; N]
     ret
 }
 
-#[repr(C, packed)]
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub struct Mechanism(pub [u8; 20]);
 
 impl Mechanism {
     pub const NULL: Self = Self(zerro_padded(br#"NULL"#));
+
+    pub fn to_bytes(&self) -> [u8; 20] {
+        self.0
+    }
+
+    pub fn from_bytes(buf: [u8; 20]) -> Self {
+        Self(buf)
+    }
 }
 
-#[repr(C, packed)]
-pub struct FrameType<S: FrameSize> {
+/// A frame header: the frame flags and the size of its body.
+///
+/// Sizes up to 255 bytes are encoded on one byte. Larger sizes are encoded on 8 bytes in network
+/// byte order, with the LONG flag set.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct FrameType {
     pub flags: Flags,
-    pub size: S,
+    pub size: u64,
 }
 
-impl<T> FrameType<T>
-where
-    T: FrameSize + Copy,
-{
-    pub async fn with_stream<S: futures::Stream<Item = Result<Bytes, std::io::Error>>>(
-        self,
-        bytes: S,
-    ) -> RawFrame {
-        use futures::{pin_mut, StreamExt};
-        pin_mut!(bytes);
-        let mut buf: Vec<u8> = Vec::new();
-        while buf.len() < self.size.into() as usize {
-            if let Some(chunk) = bytes.next().await {
-                buf.extend(chunk.unwrap());
-            } else {
-                break;
-            }
-        }
-        if self.flags.is_command() {
-            RawFrame::Command(buf)
+impl FrameType {
+    /// Maximal length of an encoded header.
+    pub const MAX_LEN: usize = 9;
+
+    /// The header of a frame of `size` bytes, setting the LONG flag as needed.
+    pub fn new(flags: Flags, size: u64) -> Self {
+        let flags = if size > u8::MAX as u64 {
+            flags.big()
         } else {
-            RawFrame::Message(buf)
-        }
+            flags.small()
+        };
+        Self { flags, size }
     }
-}
 
-pub trait FrameSize: Into<u64> + Sized {}
+    /// Encode the header as sent on the wire.
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut buf = Vec::with_capacity(Self::MAX_LEN);
+        buf.push(self.flags.0);
+        if self.flags.is_big() {
+            buf.extend(self.size.to_be_bytes());
+        } else {
+            buf.push(self.size as u8);
+        }
+        buf
+    }
 
-impl FrameSize for u8 {}
-impl FrameSize for u64 {}
+    /// Parse the header starting `buf`, along with its encoded length.
+    ///
+    /// Return `Ok(None)` while the header is not fully received.
+    pub fn parse(buf: &[u8]) -> Result<Option<(Self, usize)>, ParseError> {
+        let flags = match buf.first() {
+            Some(flags) => Flags(*flags),
+            None => return Ok(None),
+        };
+        flags.validate()?;
+        Ok(if flags.is_big() {
+            buf.get(1..9).map(|raw_size| {
+                let mut size = [0u8; 8];
+                size.copy_from_slice(raw_size);
+                (
+                    Self {
+                        flags,
+                        size: u64::from_be_bytes(size),
+                    },
+                    9,
+                )
+            })
+        } else {
+            buf.get(1).map(|size| {
+                (
+                    Self {
+                        flags,
+                        size: *size as u64,
+                    },
+                    2,
+                )
+            })
+        })
+    }
+}
 
+/// A frame body, tagged by its kind, as received on the wire.
+///
+/// The body is a slice of the receive buffer: decoding does not copy it.
 pub enum RawFrame {
-    Command(Vec<u8>),
-    Message(Vec<u8>),
+    Command(Bytes),
+    /// A message frame followed by other frames of the same message.
+    More(Bytes),
+    /// The last frame of a message.
+    Message(Bytes),
+}
+
+impl RawFrame {
+    /// Split the next complete frame off the front of `buf`.
+    ///
+    /// Return `Ok(None)` while the frame is not fully received, leaving `buf` untouched.
+    pub fn decode(buf: &mut BytesMut) -> Result<Option<Self>, ParseError> {
+        let (header, header_len) = match FrameType::parse(buf)? {
+            Some(header) => header,
+            None => return Ok(None),
+        };
+        let size = usize::try_from(header.size)
+            .map_err(|_| ParseError::FrameTooLarge(header.size, usize::MAX as u64))?;
+        if buf.len() - header_len < size {
+            return Ok(None);
+        }
+        let body = buf.split_to(header_len + size).freeze().slice(header_len..);
+        Ok(Some(if header.flags.is_command() {
+            RawFrame::Command(body)
+        } else if header.flags.is_more() {
+            RawFrame::More(body)
+        } else {
+            RawFrame::Message(body)
+        }))
+    }
 }
 
-#[repr(C, packed)]
-#[derive(Debug, Default)]
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
 pub struct Flags(pub u8);
 
 impl Flags {
     const TYPE_MASK: Self = Self(0b00000100);
     const SIZE_MASK: Self = Self(0b00000010);
     const LAST: Self = Self(0b00000001);
+    const RESERVED: Self = Self(0b11111000);
 
     pub fn command(self) -> Self {
-        // set bit 2 to 1 and keep the 2 others value
-        Self(self.0 | 0b00000100)
+        // set bit 2 to 1, keep the size bit and clear the more bit: commands are single frames
+        Self((self.0 | 0b00000100) & 0b00000110)
     }
 
     pub fn message(self) -> Self {
@@ -139,9 +224,21 @@ impl Flags {
     pub fn is_last(&self) -> bool {
         self.0 & Self::LAST.0 == 0
     }
+
+    /// Check the flags follow the spec: reserved bits are unset and commands are never followed
+    /// by other frames.
+    pub fn validate(&self) -> Result<(), ParseError> {
+        if self.0 & Self::RESERVED.0 > 0 || (self.is_command() && self.is_more()) {
+            Err(ParseError::InvalidFlags(self.0))
+        } else {
+            Ok(())
+        }
+    }
 }
 
 impl Greeting {
+    /// Length of an encoded greeting.
+    pub const LEN: usize = 64;
     const SIG: [u8; 10] = [0xff, 0, 0, 0, 0, 0, 0, 0, 0, 0x7f];
     const FILLER: [u8; 31] = [0x00; 31];
 
@@ -159,6 +256,60 @@ impl Greeting {
         self.mechanism = mechanism;
         self
     }
+
+    pub fn with_as_server(mut self, as_server: bool) -> Self {
+        self.as_server = as_server.into();
+        self
+    }
+
+    pub fn version(&self) -> Version {
+        self.version
+    }
+
+    pub fn mechanism(&self) -> Mechanism {
+        self.mechanism
+    }
+
+    /// Whether the peer acts as the server of the security mechanism.
+    pub fn as_server(&self) -> bool {
+        self.as_server == 1
+    }
+
+    /// Encode the greeting as sent on the wire.
+    pub fn to_bytes(&self) -> [u8; Self::LEN] {
+        let mut buf = [0u8; Self::LEN];
+        buf[..10].copy_from_slice(&self.signature);
+        buf[10..12].copy_from_slice(&self.version.to_bytes());
+        buf[12..32].copy_from_slice(&self.mechanism.to_bytes());
+        buf[32] = self.as_server;
+        buf[33..].copy_from_slice(&self.filler);
+        buf
+    }
+
+    /// Parse the greeting starting `buf`.
+    pub fn parse(buf: &[u8]) -> Result<Self, ParseError> {
+        let buf = buf
+            .get(..Self::LEN)
+            .ok_or(ParseError::Truncated("greeting"))?;
+        // the padding of the signature is not significant, only its bounds are
+        if buf[0] != 0xff || buf[9] & 0x01 == 0 {
+            return Err(ParseError::InvalidGreeting("signature"));
+        }
+        if buf[32] > 1 {
+            return Err(ParseError::InvalidGreeting("as-server"));
+        }
+        if buf[33..] != Self::FILLER {
+            return Err(ParseError::InvalidGreeting("filler"));
+        }
+        let field = |from: usize, to: usize| &buf[from..to];
+        Ok(Self {
+            signature: field(0, 10).try_into().unwrap(),
+            version: Version::from_bytes(field(10, 12).try_into().unwrap()),
+            mechanism: Mechanism::from_bytes(field(12, 32).try_into().unwrap()),
+            as_server: buf[32],
+            filler: field(33, 64).try_into().unwrap(),
+        })
+    }
 }
 
 impl Default for Greeting {
@@ -167,36 +318,74 @@ impl Default for Greeting {
     }
 }
 
-pub(crate) trait Packet: Sized {
-    fn as_bytes(&self) -> &[u8];
-    fn from_bytes(buf: &[u8]) -> &Self;
-}
-
-impl Packet for Greeting {
-    fn as_bytes(&self) -> &[u8] {
-        unsafe { ::core::slice::from_raw_parts((self as *const Self) as *const u8, 64) }
+#[cfg(test)]
+mod tests {
+    use super::{Flags, FrameType, Greeting, Mechanism};
+    use proptest::prelude::*;
+
+    fn flags() -> impl Strategy<Value = Flags> {
+        prop_oneof![
+            Just(Flags::default().command()),
+            Just(Flags::default().message().last()),
+            Just(Flags::default().message().more()),
+        ]
     }
 
-    fn from_bytes(buf: &[u8]) -> &Self {
-        let (head, body, _tail) = unsafe { buf.align_to::<Self>() };
-        assert!(head.is_empty(), "Data was not aligned");
-        &body[0]
+    fn sizes() -> impl Strategy<Value = u64> {
+        prop_oneof![
+            prop::sample::select(vec![0, 255, 256, u32::MAX as u64, 1 << 32, u64::MAX]),
+            0..1024u64,
+            any::<u64>(),
+        ]
     }
-}
 
-impl<T: FrameSize> Packet for FrameType<T> {
-    fn as_bytes(&self) -> &[u8] {
-        unsafe {
-            ::core::slice::from_raw_parts(
-                (self as *const Self) as *const u8,
-                core::mem::size_of::<Self>(),
-            )
+    proptest! {
+        #[test]
+        fn header_round_trip(flags in flags(), size in sizes()) {
+            let header = FrameType::new(flags, size);
+            let bytes = header.to_bytes();
+            prop_assert_eq!(bytes.len(), if size > 255 { 9 } else { 2 });
+            prop_assert_eq!(Flags(bytes[0]).is_big(), size > 255);
+            prop_assert_eq!(FrameType::parse(&bytes).unwrap(), Some((header, bytes.len())));
+            for len in 0..bytes.len() {
+                prop_assert_eq!(FrameType::parse(&bytes[..len]).unwrap(), None);
+            }
         }
+
+        #[test]
+        fn flags_validation(byte in any::<u8>()) {
+            let valid = byte & 0b11111000 == 0 && byte & 0b00000101 != 0b00000101;
+            prop_assert_eq!(FrameType::parse(&[byte, 0]).is_ok(), valid);
+        }
+    }
+
+    #[test]
+    fn greeting_round_trip() {
+        let greeting = Greeting::new().with_mechanism(Mechanism::NULL);
+        let bytes = greeting.to_bytes();
+        assert_eq!(&bytes[..12], [0xff, 0, 0, 0, 0, 0, 0, 0, 0, 0x7f, 3, 0]);
+        assert_eq!(&bytes[12..17], b"NULL\0");
+        assert_eq!(Greeting::parse(&bytes).unwrap(), greeting);
+        assert!(Greeting::parse(&bytes[..63]).is_err());
+        assert!(Greeting::parse(b"GET / HTTP/1.1\r\n").is_err());
+
+        let server = Greeting::parse(&Greeting::new().with_as_server(true).to_bytes()).unwrap();
+        assert!(server.as_server());
+        let mut bytes = Greeting::new().to_bytes();
+        bytes[32] = 2;
+        assert!(Greeting::parse(&bytes).is_err());
+        let mut bytes = Greeting::new().to_bytes();
+        bytes[63] = 1;
+        assert!(Greeting::parse(&bytes).is_err());
     }
 
-    fn from_bytes(buf: &[u8]) -> &Self {
-        let (head, body, _tail) = unsafe { buf.align_to::<Self>() };
-        assert!(head.is_empty(), "Data was not aligned");
-        &body[0]
+    #[test]
+    fn network_byte_order() {
+        let long = FrameType::new(Flags::default().command(), 0x0102030405060708);
+        assert_eq!(long.to_bytes(), [0x06, 1, 2, 3, 4, 5, 6, 7, 8]);
+        let boundary = FrameType::new(Flags::default().message().more(), 256);
+        assert_eq!(boundary.to_bytes(), [0x03, 0, 0, 0, 0, 0, 0, 1, 0]);
+        let short = FrameType::new(Flags::default().message(), 255);
+        assert_eq!(short.to_bytes(), [0x00, 0xff]);
     }
 }
@@ -0,0 +1,232 @@
+//! The async runtime running the connections of the sockets.
+//!
+//! The protocol only reads and writes through the `futures` IO traits: the runtime provides the
+//! TCP transport, spawns the tasks serving the peers and times the operations out. It is chosen
+//! with the `tokio` (default), `async-std` and `smol` features, the first enabled in this order.
+use futures::future::{AbortHandle, Abortable};
+use futures::io::{AsyncRead, AsyncWrite};
+use futures::{Future, FutureExt};
+use std::io;
+use std::net::SocketAddr;
+use std::time::Duration;
+
+#[cfg(not(any(feature = "tokio", feature = "async-std", feature = "smol")))]
+compile_error!("one of the `tokio`, `async-std` or `smol` features must be enabled");
+
+/// A byte stream to a peer.
+pub trait Transport: AsyncRead + AsyncWrite + Send + Unpin + 'static {}
+
+impl<T: AsyncRead + AsyncWrite + Send + Unpin + 'static> Transport for T {}
+
+/// A connection to a peer, with its address when known.
+pub type Connection = (Box<dyn Transport>, Option<SocketAddr>);
+
+/// A spawned task, running until it completes or is aborted.
+///
+/// Dropping the task detaches it.
+pub struct Task(AbortHandle);
+
+impl Task {
+    pub fn abort(&self) {
+        self.0.abort();
+    }
+}
+
+/// Run `future` in the background.
+pub fn spawn(future: impl Future<Output = ()> + Send + 'static) -> Task {
+    let (handle, registration) = AbortHandle::new_pair();
+    imp::spawn(Abortable::new(future, registration).map(drop));
+    Task(handle)
+}
+
+/// Run `future` for at most `limit`, returning `None` past it.
+pub async fn timeout<T>(limit: Duration, future: impl Future<Output = T>) -> Option<T> {
+    imp::timeout(limit, future).await
+}
+
+/// Wait for `duration`.
+pub async fn sleep(duration: Duration) {
+    imp::sleep(duration).await
+}
+
+/// Connect to `host:port`.
+///
+/// Small frames are sent right away, like libzmq does.
+pub async fn connect(host: &str, port: u16) -> io::Result<Connection> {
+    let stream = imp::TcpStream::connect((host, port)).await?;
+    let _ = stream.set_nodelay(true);
+    let peer = stream.peer_addr().ok();
+    Ok((imp::transport(stream), peer))
+}
+
+/// A TCP listener.
+pub struct Listener(imp::TcpListener);
+
+impl Listener {
+    pub async fn bind(host: &str, port: u16) -> io::Result<Self> {
+        imp::TcpListener::bind((host, port)).await.map(Self)
+    }
+
+    pub fn local_addr(&self) -> io::Result<SocketAddr> {
+        self.0.local_addr()
+    }
+
+    /// Accept the next peer, like [`connect`] connects to one.
+    pub async fn accept(&self) -> io::Result<Connection> {
+        let (stream, peer) = self.0.accept().await?;
+        let _ = stream.set_nodelay(true);
+        Ok((imp::transport(stream), Some(peer)))
+    }
+}
+
+#[cfg(feature = "tokio")]
+mod imp {
+    use super::Transport;
+    use futures::ready;
+    use std::io::{self, IoSlice};
+    use std::pin::Pin;
+    use std::task::{Context, Poll};
+    use std::time::Duration;
+    use tokio::io::ReadBuf;
+    pub use tokio::net::{TcpListener, TcpStream};
+
+    pub fn spawn(future: impl futures::Future<Output = ()> + Send + 'static) {
+        tokio::spawn(future);
+    }
+
+    pub async fn timeout<T>(
+        limit: Duration,
+        future: impl futures::Future<Output = T>,
+    ) -> Option<T> {
+        tokio::time::timeout(limit, future).await.ok()
+    }
+
+    pub async fn sleep(duration: Duration) {
+        tokio::time::sleep(duration).await
+    }
+
+    pub fn transport(stream: TcpStream) -> Box<dyn Transport> {
+        Box::new(Compat(stream))
+    }
+
+    /// A tokio TCP stream, read and written through the `futures` IO traits.
+    ///
+    /// Unlike the `tokio-util` adapter, vectored writes are kept vectored.
+    struct Compat(TcpStream);
+
+    impl futures::io::AsyncRead for Compat {
+        fn poll_read(
+            mut self: Pin<&mut Self>,
+            cx: &mut Context<'_>,
+            buf: &mut [u8],
+        ) -> Poll<io::Result<usize>> {
+            let mut buf = ReadBuf::new(buf);
+            ready!(tokio::io::AsyncRead::poll_read(
+                Pin::new(&mut self.0),
+                cx,
+                &mut buf
+            ))?;
+            Poll::Ready(Ok(buf.filled().len()))
+        }
+    }
+
+    impl futures::io::AsyncWrite for Compat {
+        fn poll_write(
+            mut self: Pin<&mut Self>,
+            cx: &mut Context<'_>,
+            buf: &[u8],
+        ) -> Poll<io::Result<usize>> {
+            tokio::io::AsyncWrite::poll_write(Pin::new(&mut self.0), cx, buf)
+        }
+
+        fn poll_write_vectored(
+            mut self: Pin<&mut Self>,
+            cx: &mut Context<'_>,
+            bufs: &[IoSlice<'_>],
+        ) -> Poll<io::Result<usize>> {
+            tokio::io::AsyncWrite::poll_write_vectored(Pin::new(&mut self.0), cx, bufs)
+        }
+
+        fn poll_flush(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+            tokio::io::AsyncWrite::poll_flush(Pin::new(&mut self.0), cx)
+        }
+
+        fn poll_close(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+            tokio::io::AsyncWrite::poll_shutdown(Pin::new(&mut self.0), cx)
+        }
+    }
+}
+
+#[cfg(all(feature = "async-std", not(feature = "tokio")))]
+mod imp {
+    use super::Transport;
+    pub use async_std::net::{TcpListener, TcpStream};
+    use std::time::Duration;
+
+    pub fn spawn(future: impl futures::Future<Output = ()> + Send + 'static) {
+        async_std::task::spawn(future);
+    }
+
+    pub async fn timeout<T>(
+        limit: Duration,
+        future: impl futures::Future<Output = T>,
+    ) -> Option<T> {
+        async_std::future::timeout(limit, future).await.ok()
+    }
+
+    pub async fn sleep(duration: Duration) {
+        async_std::task::sleep(duration).await
+    }
+
+    pub fn transport(stream: TcpStream) -> Box<dyn Transport> {
+        Box::new(stream)
+    }
+}
+
+#[cfg(all(feature = "smol", not(any(feature = "tokio", feature = "async-std"))))]
+mod imp {
+    use super::Transport;
+    pub use smol::net::{TcpListener, TcpStream};
+    use std::time::Duration;
+
+    pub fn spawn(future: impl futures::Future<Output = ()> + Send + 'static) {
+        smol::spawn(future).detach();
+    }
+
+    pub async fn timeout<T>(
+        limit: Duration,
+        future: impl futures::Future<Output = T>,
+    ) -> Option<T> {
+        let elapsed = async {
+            smol::Timer::after(limit).await;
+            None
+        };
+        smol::future::or(async { Some(future.await) }, elapsed).await
+    }
+
+    pub async fn sleep(duration: Duration) {
+        smol::Timer::after(duration).await;
+    }
+
+    pub fn transport(stream: TcpStream) -> Box<dyn Transport> {
+        Box::new(stream)
+    }
+}
+
+#[cfg(all(test, not(feature = "tokio")))]
+mod tests {
+    use crate::sockets::{Pull, Push};
+    use bytes::Bytes;
+
+    #[test]
+    fn without_tokio() {
+        futures::executor::block_on(async {
+            let pull = Pull::new();
+            let addr = pull.bind("127.0.0.1", 0).await.unwrap();
+            let push = Push::new();
+            push.connect("127.0.0.1", addr.port()).await.unwrap();
+            push.send(Bytes::from("job")).await.unwrap();
+            assert_eq!(pull.recv().await.unwrap(), Bytes::from("job").into());
+        });
+    }
+}
@@ -0,0 +1,136 @@
+//! The CHANNEL socket type, the draft exclusive pair.
+use super::engine::{single, Sending};
+use super::SocketType;
+use crate::{Multipart, Result};
+
+use bytes::Bytes;
+use futures::{ready, Sink, Stream};
+use std::pin::Pin;
+use std::task::{Context, Poll};
+
+socket! {
+    /// A CHANNEL socket, talking with a single other CHANNEL socket.
+    ///
+    /// Like a PAIR socket, it has at most one peer: once connected or bound, any other peer is
+    /// disconnected before the handshake. Messages are single-part, multipart messages sent
+    /// by the peer are dropped.
+    pub struct Channel(peers: "CHANNEL") {
+        sending: Sending,
+    }
+}
+
+impl Channel {
+    /// Send a single-part message to the peer.
+    ///
+    /// Wait for the peer to be connected, and for its queue to have room. Fail right away on
+    /// multipart messages.
+    pub async fn send(&self, msg: impl Into<Multipart>) -> Result<()> {
+        let msg = single(SocketType::Channel, msg.into())?;
+        self.engine.send_round_robin(msg.into()).await
+    }
+
+    /// Receive the next message from the peer.
+    pub async fn recv(&self) -> Result<Bytes> {
+        loop {
+            let (_, msg) = self.engine.recv().await;
+            if let Some(part) = msg.into_single() {
+                return Ok(part);
+            }
+        }
+    }
+}
+
+impl Sink<Bytes> for Channel {
+    type Error = crate::Error;
+
+    fn poll_ready(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Result<()>> {
+        self.get_mut().sending.poll(cx)
+    }
+
+    fn start_send(self: Pin<&mut Self>, msg: Bytes) -> Result<()> {
+        let this = self.get_mut();
+        let send = this.engine.send_round_robin(msg.into());
+        this.sending.start(send);
+        Ok(())
+    }
+
+    fn poll_flush(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Result<()>> {
+        self.get_mut().sending.poll(cx)
+    }
+
+    fn poll_close(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Result<()>> {
+        self.get_mut().sending.poll(cx)
+    }
+}
+
+impl Stream for Channel {
+    type Item = Result<Bytes>;
+
+    fn poll_next(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        let this = self.get_mut();
+        loop {
+            let (_, msg) = ready!(this.engine.poll_recv(cx));
+            if let Some(part) = msg.into_single() {
+                return Poll::Ready(Some(Ok(part)));
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Channel;
+    use crate::errors::{Error, SocketError};
+    use crate::Multipart;
+    use bytes::Bytes;
+    use std::net::TcpStream;
+
+    #[tokio::test]
+    async fn exclusive_pair() {
+        let bound = Channel::new();
+        let addr = bound.bind("127.0.0.1", 0).await.unwrap();
+        let channel = Channel::new();
+        channel.connect("127.0.0.1", addr.port()).await.unwrap();
+        assert!(matches!(
+            channel.connect("127.0.0.1", addr.port()).await,
+            Err(Error::Socket(SocketError::AlreadyPaired(_)))
+        ));
+
+        channel.send(Bytes::from("ping")).await.unwrap();
+        assert_eq!(bound.recv().await.unwrap(), "ping");
+        bound.send(Bytes::from("pong")).await.unwrap();
+        assert_eq!(channel.recv().await.unwrap(), "pong");
+
+        let multipart = Multipart(vec![Bytes::from("a"), Bytes::from("b")]);
+        let err = channel.send(multipart).await.unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "Socket error, CHANNEL sockets only send single-part messages"
+        );
+    }
+
+    #[tokio::test]
+    async fn exclusive_handshake() {
+        let bound = Channel::new();
+        let addr = bound.bind("127.0.0.1", 0).await.unwrap();
+        // the bound socket handshakes with the first connection, and drops the second one
+        let stalled = TcpStream::connect(addr).unwrap();
+        let channel = Channel::new();
+        assert!(channel.connect("127.0.0.1", addr.port()).await.is_err());
+        drop(stalled);
+
+        let bound = Channel::new();
+        let addr = bound.bind("127.0.0.1", 0).await.unwrap();
+        let (first, second) = (Channel::new(), Channel::new());
+        let (a, b) = futures::join!(
+            first.connect("127.0.0.1", addr.port()),
+            second.connect("127.0.0.1", addr.port())
+        );
+        assert!(a.is_ok() != b.is_ok());
+        let paired = if a.is_ok() { &first } else { &second };
+        paired.send(Bytes::from("ping")).await.unwrap();
+        assert_eq!(bound.recv().await.unwrap(), "ping");
+        bound.send(Bytes::from("pong")).await.unwrap();
+        assert_eq!(paired.recv().await.unwrap(), "pong");
+    }
+}
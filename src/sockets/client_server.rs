@@ -0,0 +1,196 @@
+//! The CLIENT and SERVER socket types of the draft client-server pattern (RFC 41).
+use super::engine::{single, RoutingId, Sending};
+use super::SocketType;
+use crate::{Multipart, Result};
+
+use bytes::Bytes;
+use futures::{ready, Sink, Stream};
+use std::pin::Pin;
+use std::task::{Context, Poll};
+
+socket! {
+    /// A CLIENT socket, talking with SERVER sockets.
+    ///
+    /// Messages are single-part. They are sent to the connected servers in turn, and received
+    /// from all of them in the order they arrive. Multipart messages sent by servers are dropped.
+    ///
+    /// # Exemple
+    ///
+    /// ```rust,no_run
+    /// use zmtp::sockets::Client;
+    ///
+    /// # async fn run() -> zmtp::Result<()> {
+    /// let client = Client::new();
+    /// client.connect("localhost", 55555).await?;
+    /// client.send(bytes::Bytes::from("Hi!")).await?;
+    /// let reply = client.recv().await?;
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub struct Client(peers: "SERVER") {
+        sending: Sending,
+    }
+}
+
+impl Client {
+    /// Send a single-part message to the next server in turn.
+    ///
+    /// Wait for a server to be connected, and for its queue to have room. Fail right away
+    /// on multipart messages.
+    pub async fn send(&self, msg: impl Into<Multipart>) -> Result<()> {
+        let msg = single(SocketType::Client, msg.into())?;
+        self.engine.send_round_robin(msg.into()).await
+    }
+
+    /// Receive the next message from any server.
+    pub async fn recv(&self) -> Result<Bytes> {
+        loop {
+            let (_, msg) = self.engine.recv().await;
+            if let Some(part) = msg.into_single() {
+                return Ok(part);
+            }
+        }
+    }
+}
+
+impl Sink<Bytes> for Client {
+    type Error = crate::Error;
+
+    fn poll_ready(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Result<()>> {
+        self.get_mut().sending.poll(cx)
+    }
+
+    fn start_send(self: Pin<&mut Self>, msg: Bytes) -> Result<()> {
+        let this = self.get_mut();
+        let send = this.engine.send_round_robin(msg.into());
+        this.sending.start(send);
+        Ok(())
+    }
+
+    fn poll_flush(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Result<()>> {
+        self.get_mut().sending.poll(cx)
+    }
+
+    fn poll_close(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Result<()>> {
+        self.get_mut().sending.poll(cx)
+    }
+}
+
+impl Stream for Client {
+    type Item = Result<Bytes>;
+
+    fn poll_next(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        let this = self.get_mut();
+        loop {
+            let (_, msg) = ready!(this.engine.poll_recv(cx));
+            if let Some(part) = msg.into_single() {
+                return Poll::Ready(Some(Ok(part)));
+            }
+        }
+    }
+}
+
+socket! {
+    /// A SERVER socket, talking with CLIENT sockets.
+    ///
+    /// Messages are single-part. Each received message comes with the routing id of the client
+    /// which sent it, and replies are sent to the client with this routing id. Multipart messages
+    /// sent by clients are dropped.
+    pub struct Server(peers: "CLIENT") {
+        sending: Sending,
+    }
+}
+
+impl Server {
+    /// Send a single-part message to the client with the `routing_id`.
+    ///
+    /// Wait for the client queue to have room. Fail right away on multipart messages, and
+    /// if no connected client has this routing id.
+    pub async fn send(&self, routing_id: RoutingId, msg: impl Into<Multipart>) -> Result<()> {
+        let msg = single(SocketType::Server, msg.into())?;
+        self.engine.send_to(routing_id, msg.into()).await
+    }
+
+    /// Receive the next message from any client, along with the client routing id.
+    pub async fn recv(&self) -> Result<(RoutingId, Bytes)> {
+        loop {
+            let (routing_id, msg) = self.engine.recv().await;
+            if let Some(part) = msg.into_single() {
+                return Ok((routing_id, part));
+            }
+        }
+    }
+}
+
+impl Sink<(RoutingId, Bytes)> for Server {
+    type Error = crate::Error;
+
+    fn poll_ready(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Result<()>> {
+        self.get_mut().sending.poll(cx)
+    }
+
+    fn start_send(self: Pin<&mut Self>, (routing_id, msg): (RoutingId, Bytes)) -> Result<()> {
+        let this = self.get_mut();
+        let send = this.engine.send_to(routing_id, msg.into());
+        this.sending.start(send);
+        Ok(())
+    }
+
+    fn poll_flush(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Result<()>> {
+        self.get_mut().sending.poll(cx)
+    }
+
+    fn poll_close(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Result<()>> {
+        self.get_mut().sending.poll(cx)
+    }
+}
+
+impl Stream for Server {
+    type Item = Result<(RoutingId, Bytes)>;
+
+    fn poll_next(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        let this = self.get_mut();
+        loop {
+            let (routing_id, msg) = ready!(this.engine.poll_recv(cx));
+            if let Some(part) = msg.into_single() {
+                return Poll::Ready(Some(Ok((routing_id, part))));
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Client, Server};
+    use crate::errors::{Error, SocketError};
+    use crate::Multipart;
+    use bytes::Bytes;
+
+    #[tokio::test]
+    async fn round_trip() {
+        fn thread_safe<T: Send + Sync>() {}
+        thread_safe::<Client>();
+        thread_safe::<Server>();
+
+        let server = Server::new();
+        let addr = server.bind("127.0.0.1", 0).await.unwrap();
+        let client = Client::new();
+        client.connect("127.0.0.1", addr.port()).await.unwrap();
+
+        client.send(Bytes::from("ping")).await.unwrap();
+        let (routing_id, msg) = server.recv().await.unwrap();
+        assert_eq!(msg, "ping");
+        server.send(routing_id, Bytes::from("pong")).await.unwrap();
+        assert_eq!(client.recv().await.unwrap(), "pong");
+
+        let multipart = Multipart(vec![Bytes::from("a"), Bytes::from("b")]);
+        assert!(matches!(
+            client.send(multipart).await,
+            Err(Error::Socket(SocketError::MultipartNotAllowed(_)))
+        ));
+        assert!(matches!(
+            server.send(routing_id + 1, Bytes::new()).await,
+            Err(Error::Socket(SocketError::UnknownRoutingId(_)))
+        ));
+    }
+}
@@ -0,0 +1,660 @@
+//! Peers of the sockets connecting to and accepting many peers at once.
+//!
+//! Each peer connection is served by a reading task, queuing the received messages for the
+//! socket, and a writing task, sending the messages the socket queued for this peer.
+use super::monitor::Probe;
+use super::{connect, handshake, states, EventKind, Monitor, Options, SocketType};
+use crate::errors::{ConnectionError, SocketError};
+use crate::packets::null;
+use crate::runtime::{self, Listener, Task};
+use crate::{Multipart, Result};
+
+use async_channel::{Receiver, SendError, Sender};
+use bytes::Bytes;
+use event_listener::Event;
+use futures::channel::{mpsc, oneshot};
+use futures::future::BoxFuture;
+use futures::{ready, Future, FutureExt, SinkExt, StreamExt};
+use std::collections::{BTreeMap, BTreeSet, HashSet};
+use std::net::SocketAddr;
+use std::sync::{Arc, Mutex, Weak};
+use std::task::{Context, Poll};
+use std::time::Duration;
+
+/// Identify a peer of a socket. Never zero, like the libzmq routing ids.
+pub type RoutingId = u32;
+
+/// The peers of a socket, and the messages they sent.
+pub(crate) struct Engine {
+    shared: Arc<Shared>,
+    /// The messages of every peer, in a `futures` channel which receives large messages
+    /// faster than the channels of the peer queues.
+    inbound: futures::lock::Mutex<mpsc::Receiver<(RoutingId, Multipart)>>,
+}
+
+impl Engine {
+    pub fn new(socket_type: SocketType, options: Options) -> Self {
+        let (inbound, receiver) = mpsc::channel(states::FrameWriter::HIGH_WATER_MARK);
+        Self {
+            shared: Arc::new(Shared {
+                socket_type,
+                options,
+                peers: Mutex::default(),
+                attached: Event::new(),
+                subscribed: Event::new(),
+                inbound,
+                endpoints: Mutex::default(),
+                listeners: Mutex::default(),
+            }),
+            inbound: futures::lock::Mutex::new(receiver),
+        }
+    }
+
+    pub fn monitor(&self) -> Monitor {
+        self.shared.options.monitor.clone()
+    }
+
+    /// Connect to `tcp://host:port`, returning the routing id of the peer.
+    pub async fn connect(&self, host: &str, port: u16) -> Result<RoutingId> {
+        let slot = self.shared.reserve()?;
+        let (peer, probe) =
+            connect(host, port, self.shared.socket_type, &self.shared.options).await?;
+        let id = self.shared.attach(peer, probe.clone(), slot);
+        self.shared.endpoints.lock().unwrap().push(probe);
+        Ok(id)
+    }
+
+    /// Accept peers on `tcp://host:port`, returning the bound address.
+    ///
+    /// Bind to the port `0` to get one from the operating system.
+    pub async fn bind(&self, host: &str, port: u16) -> Result<SocketAddr> {
+        let listener = Listener::bind(host, port)
+            .await
+            .map_err(|_| ConnectionError::UnbindableHost(host.to_string(), port))?;
+        let addr = listener.local_addr().map_err(ConnectionError::from)?;
+        let probe = Probe::new(self.shared.options.monitor.clone(), host, addr.port());
+        probe.emit(EventKind::Listening);
+        let accepting = runtime::spawn(accept(
+            Arc::downgrade(&self.shared),
+            listener,
+            probe.clone(),
+        ));
+        self.shared.listeners.lock().unwrap().push(accepting);
+        self.shared.endpoints.lock().unwrap().push(probe);
+        Ok(addr)
+    }
+
+    /// Send `msg` to the peer `id`, waiting while its queue is full.
+    pub fn send_to(&self, id: RoutingId, msg: Multipart) -> impl Future<Output = Result<()>> {
+        let outbound = self.shared.outbound(id);
+        async move {
+            let outbound = outbound.ok_or(SocketError::UnknownRoutingId(id))?;
+            outbound
+                .send(Outbound::Message(msg))
+                .await
+                .map_err(|_| SocketError::UnknownRoutingId(id).into())
+        }
+    }
+
+    /// Send `msg` to the next peer in turn, waiting for a peer if there are none.
+    pub fn send_round_robin(&self, msg: Multipart) -> impl Future<Output = Result<()>> {
+        let shared = self.shared.clone();
+        async move {
+            let mut msg = Outbound::Message(msg);
+            loop {
+                let attached = shared.attached.listen();
+                let next = shared.peers.lock().unwrap().next();
+                match next {
+                    // the peer may be gone in the meantime, then try the next one
+                    Some(outbound) => match outbound.send(msg).await {
+                        Ok(()) => return Ok(()),
+                        Err(SendError(unsent)) => msg = unsent,
+                    },
+                    None => attached.await,
+                }
+            }
+        }
+    }
+
+    /// Send `msg` to every peer which joined `group`, dropping it for the peers whose queue
+    /// is full.
+    pub fn publish(&self, group: &[u8], msg: Multipart) {
+        let peers = self.shared.peers.lock().unwrap();
+        for peer in peers
+            .map
+            .values()
+            .filter(|peer| peer.groups.contains(group))
+        {
+            let _ = peer.outbound.try_send(Outbound::Message(msg.clone()));
+        }
+    }
+
+    /// Send `msg` to every peer subscribed to a prefix of `data`, dropping it for the peers
+    /// whose queue is full.
+    pub fn broadcast(&self, data: &[u8], msg: Multipart) {
+        let peers = self.shared.peers.lock().unwrap();
+        for peer in peers
+            .map
+            .values()
+            .filter(|peer| peer.groups.iter().any(|topic| data.starts_with(topic)))
+        {
+            let _ = peer.outbound.try_send(Outbound::Message(msg.clone()));
+        }
+    }
+
+    /// Subscribe to `topic`, telling the current and future peers.
+    pub fn subscribe(&self, topic: &[u8]) {
+        let mut peers = self.shared.peers.lock().unwrap();
+        if peers.subscriptions.insert(topic.to_vec()) {
+            peers.tell(|| subscription(self.shared.socket_type, topic, true));
+        }
+    }
+
+    /// Cancel the subscription to `topic`, telling the peers.
+    pub fn unsubscribe(&self, topic: &[u8]) {
+        let mut peers = self.shared.peers.lock().unwrap();
+        if peers.subscriptions.remove(topic) {
+            peers.tell(|| subscription(self.shared.socket_type, topic, false));
+        }
+    }
+
+    /// Wait until a peer joined `group`, or subscribed to exactly this topic, or until none
+    /// did if not `joined`.
+    #[cfg(test)]
+    pub async fn member(&self, group: &[u8], joined: bool) {
+        loop {
+            let subscribed = self.shared.subscribed.listen();
+            let member = {
+                let peers = self.shared.peers.lock().unwrap();
+                peers.map.values().any(|peer| peer.groups.contains(group))
+            };
+            if member == joined {
+                return;
+            }
+            subscribed.await;
+        }
+    }
+
+    /// Whether the socket subscribed to exactly `topic`.
+    pub fn is_subscribed(&self, topic: &[u8]) -> bool {
+        let peers = self.shared.peers.lock().unwrap();
+        peers.subscriptions.contains(topic)
+    }
+
+    /// Whether the socket subscribed to a prefix of `data`.
+    pub fn matches(&self, data: &[u8]) -> bool {
+        let peers = self.shared.peers.lock().unwrap();
+        peers
+            .subscriptions
+            .iter()
+            .any(|topic| data.starts_with(topic))
+    }
+
+    /// Receive the next message of any peer, along with the peer routing id.
+    ///
+    /// Messages are received in the order they arrived, whichever peer sent them.
+    pub async fn recv(&self) -> (RoutingId, Multipart) {
+        let received = self.inbound.lock().await.next().await;
+        received.expect("the socket keeps a sender")
+    }
+
+    /// Stop accepting peers and close the connections, sending the queued messages first.
+    ///
+    /// Every connection keeps sending its queued messages for at most the linger duration of
+    /// the socket [`Options`]. Return the number of messages discarded because they couldn't
+    /// be sent in time.
+    pub async fn close(self) -> usize {
+        for listener in self.shared.listeners.lock().unwrap().drain(..) {
+            listener.abort();
+        }
+        let peers = std::mem::take(&mut self.shared.peers.lock().unwrap().map);
+        let closing = peers.into_values().map(Peer::close);
+        futures::future::join_all(closing).await.into_iter().sum()
+    }
+
+    /// Poll the next message of any peer, like [`Engine::recv`].
+    pub fn poll_recv(&mut self, cx: &mut Context<'_>) -> Poll<(RoutingId, Multipart)> {
+        let received = ready!(self.inbound.get_mut().poll_next_unpin(cx));
+        Poll::Ready(received.expect("the socket keeps a sender"))
+    }
+}
+
+/// The state shared with the tasks serving the peers.
+///
+/// The tasks only keep a weak reference, so dropping the socket closes it.
+struct Shared {
+    socket_type: SocketType,
+    options: Options,
+    peers: Mutex<Peers>,
+    /// Notified when a peer is attached.
+    attached: Event,
+    /// Notified when a peer changes the groups or topics it subscribed to.
+    subscribed: Event,
+    inbound: mpsc::Sender<(RoutingId, Multipart)>,
+    /// The endpoints the socket is connected or bound to, to publish their closing.
+    endpoints: Mutex<Vec<Probe>>,
+    listeners: Mutex<Vec<Task>>,
+}
+
+impl Shared {
+    /// Reserve a place for a new connection, before its handshake.
+    ///
+    /// Sockets of an exclusive pair handshake with a single peer at a time, and refuse new
+    /// connections once they have a peer, so both ends of the pair keep the same connection.
+    fn reserve(self: &Arc<Self>) -> core::result::Result<Slot, SocketError> {
+        if !matches!(self.socket_type, SocketType::Pair | SocketType::Channel) {
+            return Ok(Slot(None));
+        }
+        let mut peers = self.peers.lock().unwrap();
+        if peers.handshaking || !peers.map.is_empty() {
+            return Err(SocketError::AlreadyPaired(self.socket_type));
+        }
+        peers.handshaking = true;
+        Ok(Slot(Some(Arc::downgrade(self))))
+    }
+
+    /// Serve a peer which completed the handshake in the `slot` reserved for it, returning its
+    /// routing id.
+    fn attach(self: &Arc<Self>, peer: states::Handshaken, probe: Probe, slot: Slot) -> RoutingId {
+        let mut peers = self.peers.lock().unwrap();
+        let (outbound, queue) = async_channel::bounded(states::FrameWriter::HIGH_WATER_MARK);
+        let (subscriptions, subscribing) = async_channel::unbounded();
+        let (closing, closed) = oneshot::channel();
+        let (written, discarded) = oneshot::channel();
+        let id = peers.next_id();
+        for topic in &peers.subscriptions {
+            let _ = subscriptions.try_send(subscription(self.socket_type, topic, true));
+        }
+        let writing = write(peer.writer, subscribing, queue, closed, self.options.linger);
+        runtime::spawn(async move {
+            let _ = written.send(writing.await);
+        });
+        let reader = runtime::spawn(read(
+            Arc::downgrade(self),
+            self.socket_type,
+            id,
+            peer.reader,
+            self.inbound.clone(),
+            probe,
+        ));
+        peers.map.insert(
+            id,
+            Peer {
+                outbound,
+                subscriptions,
+                groups: HashSet::new(),
+                reader,
+                _closing: closing,
+                discarded: Some(discarded),
+            },
+        );
+        drop(peers);
+        drop(slot);
+        self.attached.notify(usize::MAX);
+        id
+    }
+
+    fn outbound(&self, id: RoutingId) -> Option<Sender<Outbound>> {
+        let peers = self.peers.lock().unwrap();
+        peers.map.get(&id).map(|peer| peer.outbound.clone())
+    }
+}
+
+impl Drop for Shared {
+    fn drop(&mut self) {
+        for listener in self.listeners.get_mut().unwrap().drain(..) {
+            listener.abort();
+        }
+        for endpoint in self.endpoints.get_mut().unwrap().drain(..) {
+            endpoint.emit(EventKind::Closed);
+        }
+    }
+}
+
+/// The place of a connection in an exclusive pair, freed when dropped.
+struct Slot(Option<Weak<Shared>>);
+
+impl Drop for Slot {
+    fn drop(&mut self) {
+        if let Some(shared) = self.0.take().and_then(|shared| shared.upgrade()) {
+            shared.peers.lock().unwrap().handshaking = false;
+        }
+    }
+}
+
+/// The peers of a socket, by routing id.
+#[derive(Default)]
+struct Peers {
+    map: BTreeMap<RoutingId, Peer>,
+    /// Whether a socket of an exclusive pair is handshaking with a peer.
+    handshaking: bool,
+    last_id: RoutingId,
+    /// The peer the last message was sent to in turn.
+    cursor: RoutingId,
+    /// The groups or topics the socket subscribed to.
+    subscriptions: BTreeSet<Vec<u8>>,
+}
+
+impl Peers {
+    /// A routing id not in use, skipping zero.
+    fn next_id(&mut self) -> RoutingId {
+        loop {
+            self.last_id = self.last_id.wrapping_add(1);
+            if self.last_id != 0 && !self.map.contains_key(&self.last_id) {
+                return self.last_id;
+            }
+        }
+    }
+
+    /// The queue of the next peer in turn.
+    fn next(&mut self) -> Option<Sender<Outbound>> {
+        let (id, peer) = self
+            .map
+            .range(self.cursor.wrapping_add(1)..)
+            .next()
+            .or_else(|| self.map.iter().next())?;
+        self.cursor = *id;
+        Some(peer.outbound.clone())
+    }
+
+    /// Send a subscription to every peer.
+    fn tell(&self, subscription: impl Fn() -> Outbound) {
+        for peer in self.map.values() {
+            let _ = peer.subscriptions.try_send(subscription());
+        }
+    }
+}
+
+/// How a `socket_type` socket tells a peer it subscribes to `topic`, or cancels it.
+fn subscription(socket_type: SocketType, topic: &[u8], subscribe: bool) -> Outbound {
+    let topic = topic.to_vec();
+    match socket_type {
+        SocketType::Dish if subscribe => Outbound::Command(null::Command::Join(topic)),
+        SocketType::Dish => Outbound::Command(null::Command::Leave(topic)),
+        // ZMTP 3.0 subscriptions are messages flagged by their first byte
+        _ => {
+            let flag = u8::from(subscribe);
+            let msg = Bytes::from([&[flag], &topic[..]].concat());
+            Outbound::Message(msg.into())
+        }
+    }
+}
+
+/// What the socket sends to a peer.
+enum Outbound {
+    Message(Multipart),
+    Command(null::Command),
+}
+
+impl Outbound {
+    fn queue(self, frames: &mut states::FrameWriter) {
+        match self {
+            Outbound::Message(msg) => frames.queue(msg.into_frames()),
+            Outbound::Command(command) => frames.queue([command.into()]),
+        }
+    }
+}
+
+/// A peer connection, closed when dropped.
+struct Peer {
+    outbound: Sender<Outbound>,
+    /// The subscriptions to send, in a queue of their own which is never full.
+    subscriptions: Sender<Outbound>,
+    /// The groups the peer joined, or the topics it subscribed to.
+    groups: HashSet<Vec<u8>>,
+    reader: Task,
+    /// Dropped to let the writing task linger, then close the connection.
+    _closing: oneshot::Sender<()>,
+    /// The number of messages the writing task couldn't send, taken when closing.
+    discarded: Option<oneshot::Receiver<usize>>,
+}
+
+impl Peer {
+    /// Close the connection, sending the queued messages first, and return the number of
+    /// messages discarded.
+    async fn close(mut self) -> usize {
+        let discarded = self.discarded.take();
+        drop(self);
+        match discarded {
+            Some(discarded) => discarded.await.unwrap_or(0),
+            None => 0,
+        }
+    }
+}
+
+impl Drop for Peer {
+    fn drop(&mut self) {
+        self.reader.abort();
+    }
+}
+
+/// How long accepting waits after a failure, like running out of file descriptors, before
+/// trying again.
+const ACCEPT_BACKOFF: Duration = Duration::from_millis(100);
+
+/// Accept the peers connecting to `listener`, until the socket is dropped.
+async fn accept(shared: Weak<Shared>, listener: Listener, probe: Probe) {
+    loop {
+        let connected = match states::Connected::accept(&listener).await {
+            Ok(connected) => connected,
+            Err(err) => {
+                probe.emit(EventKind::AcceptFailed(err));
+                runtime::sleep(ACCEPT_BACKOFF).await;
+                continue;
+            }
+        };
+        let mut probe = probe.clone();
+        probe.peer = connected.peer_addr();
+        probe.emit(EventKind::Accepted);
+        let Some(bound) = shared.upgrade() else {
+            return;
+        };
+        let slot = match bound.reserve() {
+            Ok(slot) => slot,
+            Err(err) => {
+                // dropping the connection fails the handshake of the peer too
+                probe.emit(EventKind::Disconnected(Some(err.into())));
+                continue;
+            }
+        };
+        let (socket_type, options) = (bound.socket_type, bound.options.clone());
+        drop(bound);
+        let shared = shared.clone();
+        runtime::spawn(async move {
+            if let Ok(peer) = handshake(connected, socket_type, &options, &probe).await {
+                if let Some(shared) = shared.upgrade() {
+                    shared.attach(peer, probe, slot);
+                }
+            }
+        });
+    }
+}
+
+/// Queue the messages of the peer `id` for the socket, until the connection is lost.
+///
+/// The subscriptions sent to publishers are kept, XPUB sockets also queuing the ones changing
+/// the topics subscribed by any peer.
+async fn read(
+    shared: Weak<Shared>,
+    socket_type: SocketType,
+    id: RoutingId,
+    mut frames: states::FrameReader,
+    mut inbound: mpsc::Sender<(RoutingId, Multipart)>,
+    probe: Probe,
+) {
+    let publisher = matches!(socket_type, SocketType::Pub | SocketType::XPub);
+    let mut parts = Vec::new();
+    let cause = loop {
+        let frame = match frames.next().await {
+            Some(Ok(frame)) => frame,
+            Some(Err(err)) => break Some(err),
+            None => break None,
+        };
+        let msg = match frame {
+            null::Frame::Command(null::Command::Join(group)) => {
+                subscribe(&shared, id, group, true);
+                continue;
+            }
+            null::Frame::Command(null::Command::Leave(group)) => {
+                subscribe(&shared, id, group, false);
+                continue;
+            }
+            null::Frame::Command(_) => continue,
+            null::Frame::Separator => {
+                parts.push(Bytes::new());
+                continue;
+            }
+            null::Frame::More(part) => {
+                parts.push(part);
+                continue;
+            }
+            null::Frame::Message(part) => {
+                parts.push(part);
+                Multipart(std::mem::take(&mut parts))
+            }
+        };
+        if publisher {
+            let Some((topic, subscribing)) = parse_subscription(&msg) else {
+                continue;
+            };
+            let changed = subscribe(&shared, id, topic.to_vec(), subscribing);
+            if !(changed && socket_type == SocketType::XPub) {
+                continue;
+            }
+        }
+        if inbound.send((id, msg)).await.is_err() {
+            return;
+        }
+    };
+    probe.emit(EventKind::Disconnected(cause));
+    if let Some(shared) = shared.upgrade() {
+        shared.peers.lock().unwrap().map.remove(&id);
+    }
+}
+
+/// The topic of a subscription sent to a publisher, and whether it subscribes or cancels.
+pub fn parse_subscription(msg: &Multipart) -> Option<(&[u8], bool)> {
+    match &msg.0[..] {
+        [part] => match part.split_first()? {
+            (1, topic) => Some((topic, true)),
+            (0, topic) => Some((topic, false)),
+            _ => None,
+        },
+        _ => None,
+    }
+}
+
+/// Update the groups or topics the peer `id` subscribed to.
+///
+/// Return whether the peer is the first to subscribe to the topic, or the last to cancel it.
+fn subscribe(shared: &Weak<Shared>, id: RoutingId, topic: Vec<u8>, subscribing: bool) -> bool {
+    let Some(shared) = shared.upgrade() else {
+        return false;
+    };
+    let mut peers = shared.peers.lock().unwrap();
+    let Some(peer) = peers.map.get_mut(&id) else {
+        return false;
+    };
+    let updated = if subscribing {
+        peer.groups.insert(topic.clone())
+    } else {
+        peer.groups.remove(&topic)
+    };
+    shared.subscribed.notify(usize::MAX);
+    let subscribers = peers
+        .map
+        .values()
+        .filter(|peer| peer.groups.contains(&topic));
+    updated && subscribers.count() == usize::from(subscribing)
+}
+
+/// Send the subscriptions and the messages the socket queued for a peer, the subscriptions
+/// first.
+///
+/// Once `closed`, keep sending the queued messages for at most `linger`, then shut the
+/// connection down. Return the number of messages which couldn't be sent.
+async fn write(
+    mut frames: states::FrameWriter,
+    subscriptions: Receiver<Outbound>,
+    queue: Receiver<Outbound>,
+    mut closed: oneshot::Receiver<()>,
+    linger: Option<Duration>,
+) -> usize {
+    loop {
+        let msg = futures::select_biased! {
+            subscription = subscriptions.recv().fuse() => subscription.ok(),
+            msg = queue.recv().fuse() => msg.ok(),
+            _ = closed => None,
+        };
+        let Some(msg) = msg else {
+            break;
+        };
+        msg.queue(&mut frames);
+        // write the messages queued meanwhile at once
+        while frames.queued() < states::FrameWriter::HIGH_WATER_MARK {
+            match subscriptions.try_recv().or_else(|_| queue.try_recv()) {
+                Ok(msg) => msg.queue(&mut frames),
+                Err(_) => break,
+            }
+        }
+        if frames.flush().await.is_err() {
+            return discard(&frames, &queue);
+        }
+    }
+    let lingering = async {
+        queue.close();
+        while let Ok(msg) = queue.recv().await {
+            msg.queue(&mut frames);
+        }
+        frames.flush().await
+    };
+    let flushed = match linger {
+        Some(linger) => runtime::timeout(linger, lingering).await,
+        None => Some(lingering.await),
+    };
+    let discarded = match flushed {
+        Some(Ok(())) => 0,
+        _ => discard(&frames, &queue),
+    };
+    let _ = frames.shutdown().await;
+    discarded
+}
+
+/// Count the messages left to send to a peer, the ones partly written included.
+fn discard(frames: &states::FrameWriter, queue: &Receiver<Outbound>) -> usize {
+    queue.close();
+    let mut discarded = frames.queued();
+    while queue.try_recv().is_ok() {
+        discarded += 1;
+    }
+    discarded
+}
+
+/// The only part of a message sent on a `socket_type` socket.
+pub fn single(socket_type: SocketType, msg: Multipart) -> Result<Bytes> {
+    match msg.into_single() {
+        Some(part) => Ok(part),
+        None => Err(SocketError::MultipartNotAllowed(socket_type).into()),
+    }
+}
+
+/// A send started by [`Sink::start_send`](futures::Sink::start_send), driven to completion
+/// by the next calls of the sink.
+#[derive(Default)]
+pub(crate) struct Sending(Mutex<Option<BoxFuture<'static, Result<()>>>>);
+
+impl Sending {
+    pub fn start(&mut self, send: impl Future<Output = Result<()>> + Send + 'static) {
+        *self.0.get_mut().unwrap() = Some(Box::pin(send));
+    }
+
+    pub fn poll(&mut self, cx: &mut Context<'_>) -> Poll<Result<()>> {
+        let sending = self.0.get_mut().unwrap();
+        if let Some(send) = sending {
+            let sent = ready!(send.as_mut().poll(cx));
+            *sending = None;
+            return Poll::Ready(sent);
+        }
+        Poll::Ready(Ok(()))
+    }
+}
@@ -0,0 +1,891 @@
+//! Zmtp provided sockets (base, plain password, curve)
+//!
+//! Besides [`Zmtp`] and [`Rep`], the sockets are thread safe: every method takes `&self`, so a
+//! socket can be shared between tasks with an [`Arc`](std::sync::Arc).
+use crate::connection::{Connection, Limits};
+use crate::errors::{ConnectionError, ParseError, ReuniteError};
+use crate::packets::{null, Greeting};
+use crate::{Multipart, Result};
+
+use bytes::Bytes;
+use futures::{ready, Sink, Stream, StreamExt};
+use std::pin::Pin;
+use std::task::{Context, Poll};
+use std::time::Duration;
+
+/// Define a socket served by an engine, with its constructors and its `connect`, `bind`,
+/// `monitor` and `close` methods, like `blocking_socket!` does for the blocking sockets.
+///
+/// The socket type has the name of the socket, `peers` names the socket types it talks with.
+/// The fields following the engine start with their default value.
+macro_rules! socket {
+    (
+        $(#[$doc:meta])*
+        pub struct $name:ident(peers: $peers:literal) {
+            $($(#[$field_doc:meta])* $field:ident: $ty:ty,)*
+        }
+    ) => {
+        socket! {
+            @define $(#[$doc])* $name($peers) { $($(#[$field_doc])* $field: $ty,)* }
+            connect -> () = drop,
+            concat!("Connect to the ", $peers, " socket at `tcp://host:port`.")
+        }
+    };
+    (
+        $(#[$doc:meta])*
+        pub struct $name:ident(peers: $peers:literal, connect -> RoutingId) {
+            $($(#[$field_doc:meta])* $field:ident: $ty:ty,)*
+        }
+    ) => {
+        socket! {
+            @define $(#[$doc])* $name($peers) { $($(#[$field_doc])* $field: $ty,)* }
+            connect -> $crate::sockets::RoutingId = std::convert::identity,
+            concat!(
+                "Connect to the ", $peers, " socket at `tcp://host:port`, returning its routing id."
+            )
+        }
+    };
+    (
+        @define $(#[$doc:meta])* $name:ident($peers:literal) {
+            $($(#[$field_doc:meta])* $field:ident: $ty:ty,)*
+        }
+        connect -> $connected:ty = $map:expr, $connect_doc:expr
+    ) => {
+        $(#[$doc])*
+        pub struct $name {
+            engine: $crate::sockets::engine::Engine,
+            $($(#[$field_doc])* $field: $ty,)*
+        }
+
+        impl Default for $name {
+            fn default() -> Self {
+                Self::new()
+            }
+        }
+
+        impl $name {
+            pub fn new() -> Self {
+                Self::with_options($crate::sockets::Options::default())
+            }
+
+            pub fn with_options(options: $crate::sockets::Options) -> Self {
+                Self {
+                    engine: $crate::sockets::engine::Engine::new(
+                        $crate::sockets::SocketType::$name,
+                        options,
+                    ),
+                    $($field: Default::default(),)*
+                }
+            }
+
+            #[doc = $connect_doc]
+            pub async fn connect(&self, host: &str, port: u16) -> $crate::Result<$connected> {
+                self.engine.connect(host, port).await.map($map)
+            }
+
+            #[doc = concat!(
+                "Accept ", $peers, " sockets on `tcp://host:port`, returning the bound address."
+            )]
+            pub async fn bind(&self, host: &str, port: u16) -> $crate::Result<std::net::SocketAddr> {
+                self.engine.bind(host, port).await
+            }
+
+            /// Return the monitor publishing the events of this socket.
+            pub fn monitor(&self) -> $crate::sockets::Monitor {
+                self.engine.monitor()
+            }
+
+            /// Close the socket, sending the queued messages first.
+            ///
+            /// Each connection keeps sending its queued messages for at most the linger
+            /// duration of the socket [`Options`](crate::sockets::Options), then is shut down.
+            /// Return the number of messages discarded because they couldn't be sent in time.
+            pub async fn close(self) -> usize {
+                self.engine.close().await
+            }
+        }
+    };
+}
+
+mod channel;
+mod client_server;
+mod engine;
+pub mod monitor;
+mod peer;
+mod pipeline;
+mod proxy;
+mod pub_sub;
+mod radio_dish;
+mod request_reply;
+mod scatter_gather;
+mod socket_type;
+pub use self::channel::Channel;
+pub use self::client_server::{Client, Server};
+pub use self::engine::RoutingId;
+use self::monitor::Probe;
+pub use self::monitor::{Event, EventKind, Monitor};
+pub use self::peer::Peer;
+pub use self::pipeline::{Pull, Push};
+pub use self::proxy::proxy;
+pub use self::pub_sub::{Pub, Sub, XPub, XSub};
+pub use self::radio_dish::{Dish, Radio, MAX_GROUP_LEN};
+pub use self::request_reply::{Dealer, Rep, Router};
+pub use self::scatter_gather::{Gather, Scatter};
+pub use self::socket_type::SocketType;
+
+/// Options of a socket, given when it connects.
+#[derive(Debug, Clone)]
+pub struct Options {
+    monitor: Monitor,
+    linger: Option<Duration>,
+    connect_timeout: Option<Duration>,
+    reconnect_interval: Option<Duration>,
+    handshake_timeout: Option<Duration>,
+    max_frame_size: Option<u64>,
+    max_message_size: Option<u64>,
+}
+
+impl Default for Options {
+    fn default() -> Self {
+        Self {
+            monitor: Monitor::default(),
+            linger: None,
+            connect_timeout: None,
+            reconnect_interval: None,
+            handshake_timeout: Some(Self::HANDSHAKE_TIMEOUT),
+            max_frame_size: None,
+            max_message_size: None,
+        }
+    }
+}
+
+impl Options {
+    /// Default handshake timeout, like the default `ZMQ_HANDSHAKE_IVL`.
+    pub const HANDSHAKE_TIMEOUT: Duration = Duration::from_secs(30);
+
+    /// Give up establishing the TCP connection after `timeout`.
+    ///
+    /// By default, it is up to the operating system.
+    pub fn with_connect_timeout(mut self, timeout: Duration) -> Self {
+        self.connect_timeout = Some(timeout);
+        self
+    }
+
+    /// Try to establish the TCP connection again `interval` after a failed attempt, like
+    /// `ZMQ_RECONNECT_IVL`, until it succeeds.
+    ///
+    /// By default, connecting fails at the first failed attempt.
+    pub fn with_reconnect_interval(mut self, interval: Duration) -> Self {
+        self.reconnect_interval = Some(interval);
+        self
+    }
+
+    /// Give up the ZMTP handshake with the peer after `timeout`.
+    ///
+    /// Default to [`Options::HANDSHAKE_TIMEOUT`].
+    pub fn with_handshake_timeout(mut self, timeout: Duration) -> Self {
+        self.handshake_timeout = Some(timeout);
+        self
+    }
+
+    /// Wait at most `linger` for the queued messages to be sent when the socket is closed.
+    ///
+    /// By default, closing waits until every queued message is sent.
+    pub fn with_linger(mut self, linger: Duration) -> Self {
+        self.linger = Some(linger);
+        self
+    }
+
+    /// Disconnect peers sending a frame, commands included, larger than `size` bytes.
+    ///
+    /// The size is checked from the frame header, before the frame is buffered.
+    pub fn with_max_frame_size(mut self, size: u64) -> Self {
+        self.max_frame_size = Some(size);
+        self
+    }
+
+    /// Disconnect peers sending a message larger than `size` bytes, like `ZMQ_MAXMSGSIZE`.
+    ///
+    /// The size of a multipart message is the sum of its frames sizes. It is checked from each
+    /// frame header, before the frame is buffered.
+    pub fn with_max_message_size(mut self, size: u64) -> Self {
+        self.max_message_size = Some(size);
+        self
+    }
+
+    /// Publish the socket events on `monitor`, including the ones of the connection.
+    pub fn with_monitor(mut self, monitor: Monitor) -> Self {
+        self.monitor = monitor;
+        self
+    }
+}
+
+/// Run `fut` for at most `limit`, failing with `timed_out` past it.
+async fn within<T>(
+    limit: Option<Duration>,
+    fut: impl core::future::Future<Output = core::result::Result<T, ConnectionError>>,
+    timed_out: ConnectionError,
+) -> core::result::Result<T, ConnectionError> {
+    match limit {
+        Some(limit) => crate::runtime::timeout(limit, fut)
+            .await
+            .unwrap_or(Err(timed_out)),
+        None => fut.await,
+    }
+}
+
+/// Connect to `tcp://host:port` and handshake as a `socket_type` socket.
+async fn connect(
+    host: &str,
+    port: u16,
+    socket_type: SocketType,
+    options: &Options,
+) -> core::result::Result<(states::Handshaken, Probe), ConnectionError> {
+    let mut probe = Probe::new(options.monitor.clone(), host, port);
+    let connected = loop {
+        let attempt = within(
+            options.connect_timeout,
+            states::Connected::connect(host, port),
+            ConnectionError::ConnectTimeout(host.to_string(), port),
+        );
+        let mut attempt = std::pin::pin!(attempt);
+        let attempt = match futures::poll!(attempt.as_mut()) {
+            Poll::Ready(attempt) => attempt,
+            Poll::Pending => {
+                probe.emit(EventKind::ConnectDelayed);
+                attempt.await
+            }
+        };
+        match attempt {
+            Ok(connected) => break connected,
+            Err(err) => {
+                probe.emit(EventKind::ConnectFailed(err.clone()));
+                let Some(interval) = options.reconnect_interval else {
+                    return Err(err);
+                };
+                crate::runtime::sleep(interval).await;
+                probe.emit(EventKind::ConnectRetried);
+            }
+        }
+    };
+    probe.peer = connected.peer_addr();
+    probe.emit(EventKind::Connected);
+    let peer = handshake(connected, socket_type, options, &probe).await?;
+    Ok((peer, probe))
+}
+
+/// Handshake as a `socket_type` socket over a new connection, publishing the outcome.
+async fn handshake(
+    connected: states::Connected,
+    socket_type: SocketType,
+    options: &Options,
+    probe: &Probe,
+) -> core::result::Result<states::Handshaken, ConnectionError> {
+    let limits = Limits {
+        frame: options.max_frame_size,
+        message: options.max_message_size,
+    };
+    let handshake = connected.handshake(Connection::new(socket_type).with_limits(limits));
+    match within(
+        options.handshake_timeout,
+        handshake,
+        ConnectionError::HandshakeTimeout(),
+    )
+    .await
+    {
+        Ok(peer) => {
+            probe.emit(EventKind::HandshakeSucceeded);
+            Ok(peer)
+        }
+        Err(err) => {
+            probe.emit(EventKind::HandshakeFailed(err.clone()));
+            Err(err)
+        }
+    }
+}
+
+/// The base ZMTP socket.
+///
+/// It use the ZMQ REQ comunication protocol.
+/// The authentication mechanism is NULL which does not provide any
+/// encryption/security mechanism.
+pub struct Zmtp(ZmtpSender, ZmtpReceiver);
+
+impl Zmtp {
+    /// Connect to `tcp://host:port`.
+    ///
+    /// Only provide tcp base transport for now. But will become generic over the base transport
+    /// before `v0.0.2`
+    ///
+    /// # Exemple
+    ///
+    /// ```rust
+    /// use zmtp::sockets;
+    ///
+    /// let port = 55555;
+    /// let host = "localhost";
+    /// sockets::Zmtp::connect(host, port);
+    /// ```
+    pub async fn connect(host: &str, port: u16) -> Result<Self> {
+        Self::connect_with(host, port, Options::default()).await
+    }
+
+    /// Connect to `tcp://host:port` with the given options.
+    ///
+    /// # Exemple
+    ///
+    /// ```rust
+    /// use zmtp::sockets::{Monitor, Options, Zmtp};
+    ///
+    /// let monitor = Monitor::new();
+    /// let events = monitor.events();
+    /// Zmtp::connect_with("localhost", 55555, Options::default().with_monitor(monitor));
+    /// ```
+    pub async fn connect_with(host: &str, port: u16, options: Options) -> Result<Self> {
+        let (peer, probe) = connect(host, port, SocketType::Req, &options).await?;
+        Ok(Zmtp(
+            ZmtpSender {
+                frames: peer.writer,
+                probe: probe.clone(),
+                linger: options.linger,
+                peer_greeting: peer.greeting,
+            },
+            ZmtpReceiver {
+                frames: peer.reader,
+                parts: None,
+                probe,
+                disconnected: false,
+            },
+        ))
+    }
+
+    /// Return the used version of ZMTP.
+    ///
+    /// Currently always return `3.0` because this crate does not provide back compatibility.
+    pub fn version(&self) -> crate::packets::Version {
+        crate::packets::Version { major: 3, minor: 0 }
+    }
+
+    /// Return the greeting the peer sent during the handshake.
+    pub fn peer_greeting(&self) -> &Greeting {
+        self.0.peer_greeting()
+    }
+
+    /// Return the monitor publishing the events of this socket.
+    pub fn monitor(&self) -> Monitor {
+        self.0.probe.monitor.clone()
+    }
+
+    /// Send a frame.
+    /// In the REQ protocol, it wait for a response which is returned by this function.
+    ///
+    /// A reply not starting with the empty delimiter fails with
+    /// [`ParseError::UnexpectedFrame`].
+    pub async fn send_frame(&mut self, frame: null::Frame) -> crate::Result<null::Frame> {
+        self.0.frames.send(null::Frame::Separator).await?;
+        self.0.frames.send(frame).await?;
+        if self.1.frames.recv().await? == null::Frame::Separator {
+            self.1.frames.recv().await
+        } else {
+            Err(ParseError::UnexpectedFrame("the empty delimiter").into())
+        }
+    }
+
+    /// Close the socket, sending the queued messages first.
+    ///
+    /// See [`ZmtpSender::close`].
+    pub async fn close(self) -> Result<usize> {
+        self.0.close().await
+    }
+
+    /// Split the socket into a sending and a receiving half.
+    ///
+    /// Both halves can be moved to different tasks, and put back together with
+    /// [`ZmtpSender::reunite`].
+    pub fn split(self) -> (ZmtpSender, ZmtpReceiver) {
+        (self.0, self.1)
+    }
+}
+
+impl Sink<Multipart> for Zmtp {
+    type Error = crate::Error;
+
+    fn poll_ready(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Result<()>> {
+        Pin::new(&mut self.get_mut().0).poll_ready(cx)
+    }
+
+    fn start_send(self: Pin<&mut Self>, msg: Multipart) -> Result<()> {
+        Pin::new(&mut self.get_mut().0).start_send(msg)
+    }
+
+    fn poll_flush(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Result<()>> {
+        Pin::new(&mut self.get_mut().0).poll_flush(cx)
+    }
+
+    fn poll_close(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Result<()>> {
+        Pin::new(&mut self.get_mut().0).poll_close(cx)
+    }
+}
+
+impl Stream for Zmtp {
+    type Item = Result<Multipart>;
+
+    fn poll_next(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        Pin::new(&mut self.get_mut().1).poll_next(cx)
+    }
+}
+
+/// The sending half of a [`Zmtp`] socket, created by [`Zmtp::split`].
+///
+/// Messages are queued by [`Sink::start_send`] and written by [`Sink::poll_flush`].
+pub struct ZmtpSender {
+    frames: states::FrameWriter,
+    probe: Probe,
+    linger: Option<Duration>,
+    peer_greeting: Greeting,
+}
+
+impl ZmtpSender {
+    /// Return the greeting the peer sent during the handshake.
+    pub fn peer_greeting(&self) -> &Greeting {
+        &self.peer_greeting
+    }
+
+    /// Close the socket, sending the queued messages first.
+    ///
+    /// Keep sending the queued messages for at most the linger duration of the socket
+    /// [`Options`], then shut the connection down. Return the number of messages discarded
+    /// because they couldn't be sent in time.
+    pub async fn close(mut self) -> Result<usize> {
+        let flushed = futures::future::poll_fn(|cx| self.frames.poll_flush(cx));
+        let flushed = match self.linger {
+            Some(linger) => crate::runtime::timeout(linger, flushed).await,
+            None => Some(flushed.await),
+        };
+        let discarded = match flushed {
+            Some(result) => {
+                result?;
+                0
+            }
+            None => self.frames.queued(),
+        };
+        futures::future::poll_fn(|cx| self.frames.poll_shutdown(cx)).await?;
+        self.probe.emit(EventKind::Closed);
+        Ok(discarded)
+    }
+
+    /// Put back together the halves of a socket split by [`Zmtp::split`].
+    ///
+    /// Fail if the halves don't come from the same socket.
+    #[allow(clippy::result_large_err)]
+    pub fn reunite(self, receiver: ZmtpReceiver) -> core::result::Result<Zmtp, ReuniteError> {
+        let ZmtpReceiver {
+            frames,
+            parts,
+            probe,
+            disconnected,
+        } = receiver;
+        let (frames, sender) = match states::reunite(frames, self.frames) {
+            Ok((reader, writer)) => (reader, Ok(writer)),
+            Err((reader, writer)) => (reader, Err(writer)),
+        };
+        let receiver = ZmtpReceiver {
+            frames,
+            parts,
+            probe,
+            disconnected,
+        };
+        match sender {
+            Ok(frames) => Ok(Zmtp(ZmtpSender { frames, ..self }, receiver)),
+            Err(frames) => Err(ReuniteError(ZmtpSender { frames, ..self }, receiver)),
+        }
+    }
+}
+
+impl Sink<Multipart> for ZmtpSender {
+    type Error = crate::Error;
+
+    fn poll_ready(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Result<()>> {
+        self.get_mut().frames.poll_ready(cx).map_err(Into::into)
+    }
+
+    fn start_send(self: Pin<&mut Self>, msg: Multipart) -> Result<()> {
+        self.get_mut()
+            .frames
+            .queue(core::iter::once(null::Frame::Separator).chain(msg.into_frames()));
+        Ok(())
+    }
+
+    fn poll_flush(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Result<()>> {
+        self.get_mut().frames.poll_flush(cx).map_err(Into::into)
+    }
+
+    fn poll_close(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Result<()>> {
+        let this = self.get_mut();
+        ready!(this.frames.poll_close(cx))?;
+        this.probe.emit(EventKind::Closed);
+        Poll::Ready(Ok(()))
+    }
+}
+
+/// The receiving half of a [`Zmtp`] socket, created by [`Zmtp::split`].
+///
+/// Holds the parts of the message being received, once its envelope delimiter is read.
+pub struct ZmtpReceiver {
+    frames: states::FrameReader,
+    parts: Option<Vec<Bytes>>,
+    probe: Probe,
+    disconnected: bool,
+}
+
+impl ZmtpReceiver {
+    /// Publish the loss of the connection and its cause, once.
+    fn disconnect(&mut self, cause: Option<&crate::Error>) {
+        if !self.disconnected {
+            self.disconnected = true;
+            self.probe.emit(EventKind::Disconnected(cause.cloned()));
+        }
+    }
+}
+
+impl Stream for ZmtpReceiver {
+    type Item = Result<Multipart>;
+
+    fn poll_next(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        let this = self.get_mut();
+        loop {
+            let frame = match ready!(this.frames.poll_next_unpin(cx)) {
+                Some(Ok(frame)) => frame,
+                Some(Err(err)) => {
+                    // the frames stream is over after an error
+                    this.parts = None;
+                    this.disconnect(Some(&err));
+                    return Poll::Ready(Some(Err(err)));
+                }
+                None => {
+                    this.disconnect(None);
+                    if this.parts.take().is_none() {
+                        return Poll::Ready(None);
+                    }
+                    let err = std::io::Error::new(
+                        std::io::ErrorKind::UnexpectedEof,
+                        "connection closed in the middle of a message",
+                    );
+                    return Poll::Ready(Some(Err(ConnectionError::from(err).into())));
+                }
+            };
+            match (frame, this.parts.as_mut()) {
+                (null::Frame::Command(_), _) => (),
+                (null::Frame::Separator, None) => this.parts = Some(Vec::new()),
+                (null::Frame::Separator, Some(parts)) => parts.push(Bytes::new()),
+                (null::Frame::More(part), _) => this.parts.get_or_insert_with(Vec::new).push(part),
+                (null::Frame::Message(part), _) => {
+                    let mut parts = this.parts.take().unwrap_or_default();
+                    parts.push(part);
+                    return Poll::Ready(Some(Ok(Multipart(parts))));
+                }
+            }
+        }
+    }
+}
+
+mod states {
+    use crate::connection::{Connection, Event, Transmit};
+    use crate::errors::ConnectionError;
+    use crate::packets::{null, Greeting};
+    use crate::runtime::{self, Listener, Transport};
+
+    use bytes::BytesMut;
+    use futures::io::{AsyncRead, AsyncReadExt, AsyncWrite, ReadHalf, WriteHalf};
+    use futures::{ready, Stream, StreamExt, TryFutureExt};
+    use std::io::IoSlice;
+    use std::net::SocketAddr;
+    use std::pin::Pin;
+    use std::task::{Context, Poll};
+
+    type Reader = ReadHalf<Box<dyn Transport>>;
+    type Writer = WriteHalf<Box<dyn Transport>>;
+
+    /// A new connection, before the handshake.
+    pub struct Connected {
+        stream: Box<dyn Transport>,
+        peer: Option<SocketAddr>,
+    }
+    impl Connected {
+        pub async fn connect(host: &str, port: u16) -> Result<Connected, ConnectionError> {
+            runtime::connect(host, port)
+                .map_ok(Connected::new)
+                .map_err(|_| ConnectionError::UnaccessibleHost(host.to_string(), port))
+                .await
+        }
+
+        pub async fn accept(listener: &Listener) -> Result<Connected, ConnectionError> {
+            Ok(Connected::new(listener.accept().await?))
+        }
+
+        fn new((stream, peer): runtime::Connection) -> Self {
+            Self { stream, peer }
+        }
+
+        pub fn peer_addr(&self) -> Option<SocketAddr> {
+            self.peer
+        }
+
+        /// Drive the handshake of `connection` until the peer is ready.
+        ///
+        /// When the handshake is rejected, the reason queued by the connection is sent before
+        /// giving up, ignoring failures to send it.
+        pub async fn handshake(
+            self,
+            connection: Connection,
+        ) -> Result<Handshaken, ConnectionError> {
+            let (reader, writer) = self.stream.split();
+            let mut reader = FrameReader {
+                half: reader,
+                connection,
+                spare: BytesMut::new(),
+                failed: false,
+            };
+            let mut writer = FrameWriter::new(writer);
+            loop {
+                let event = reader.connection.poll_event();
+                writer.transmit.append(reader.connection.transmit());
+                let flushed = writer.flush().await;
+                match event {
+                    Some(Event::Ready { .. }) => {
+                        flushed?;
+                        let greeting = reader.connection.peer_greeting().cloned();
+                        let greeting = greeting.expect("READY follows the greeting");
+                        return Ok(Handshaken {
+                            reader,
+                            writer,
+                            greeting,
+                        });
+                    }
+                    Some(Event::Error(crate::Error::Connection(err))) => return Err(err),
+                    Some(Event::Error(crate::Error::Parse(err))) => {
+                        return Err(ConnectionError::InvalidHandshake(err))
+                    }
+                    Some(Event::Error(crate::Error::Socket(err))) => {
+                        return Err(std::io::Error::other(err).into())
+                    }
+                    Some(_) => flushed?,
+                    None => {
+                        flushed?;
+                        let read = futures::future::poll_fn(|cx| reader.poll_read(cx)).await?;
+                        if read == 0 {
+                            return Err(
+                                std::io::Error::from(std::io::ErrorKind::UnexpectedEof).into()
+                            );
+                        }
+                    }
+                }
+            }
+        }
+    }
+
+    /// A connection ready to exchange messages.
+    pub struct Handshaken {
+        pub reader: FrameReader,
+        pub writer: FrameWriter,
+        /// The greeting the peer sent.
+        pub greeting: Greeting,
+    }
+
+    /// Put back together the halves of the same connection, or give them back.
+    #[allow(clippy::type_complexity, clippy::result_large_err)]
+    pub fn reunite(
+        reader: FrameReader,
+        writer: FrameWriter,
+    ) -> Result<(FrameReader, FrameWriter), (FrameReader, FrameWriter)> {
+        match reader.half.reunite(writer.half) {
+            Ok(stream) => {
+                let (read_half, half) = stream.split();
+                Ok((
+                    FrameReader {
+                        half: read_half,
+                        ..reader
+                    },
+                    FrameWriter { half, ..writer },
+                ))
+            }
+            Err(futures::io::ReuniteError(read_half, half)) => Err((
+                FrameReader {
+                    half: read_half,
+                    ..reader
+                },
+                FrameWriter { half, ..writer },
+            )),
+        }
+    }
+
+    /// Frames stream over the receiving half of a connection.
+    ///
+    /// Received frames are sliced out of the receive buffer of the connection, so message
+    /// bodies are never copied.
+    ///
+    /// The stream ends after the first error: a peer breaking the protocol is not read anymore.
+    pub struct FrameReader {
+        half: Reader,
+        connection: Connection,
+        /// Zeroed memory right after the receive buffer, read into then moved to the buffer.
+        ///
+        /// It is zeroed once, not before every read.
+        spare: BytesMut,
+        /// Whether reading failed.
+        failed: bool,
+    }
+    impl FrameReader {
+        /// Minimal spare memory to read into.
+        const READ_CHUNK: usize = 8 * 1024;
+        /// Minimal size of the spare memory, once refilled.
+        const SPARE: usize = 64 * 1024;
+
+        /// Receive the next frame, failing if the connection is closed.
+        pub async fn recv(&mut self) -> crate::Result<null::Frame> {
+            self.next().await.unwrap_or_else(|| {
+                Err(ConnectionError::from(std::io::Error::new(
+                    std::io::ErrorKind::UnexpectedEof,
+                    "Remote doesn't answer to the request",
+                ))
+                .into())
+            })
+        }
+
+        /// Read from the peer at the end of the receive buffer.
+        fn poll_read(&mut self, cx: &mut Context) -> Poll<std::io::Result<usize>> {
+            if self.spare.len() < Self::READ_CHUNK {
+                // give the spare memory back so that the buffer can reuse its allocation, and
+                // grow along with the bytes not decoded yet so that large frames aren't moved
+                // over and over
+                self.spare = BytesMut::new();
+                let buffer = self.connection.receive_buffer();
+                let len = buffer.len();
+                buffer.reserve(len.max(Self::SPARE));
+                buffer.resize(buffer.capacity(), 0);
+                self.spare = buffer.split_off(len);
+            }
+            let read = Pin::new(&mut self.half).poll_read(cx, &mut self.spare);
+            if let Poll::Ready(Ok(filled)) = read {
+                // the spare memory follows the buffer: appending it doesn't copy it
+                let filled = self.spare.split_to(filled);
+                self.connection.receive_buffer().unsplit(filled);
+            }
+            read
+        }
+    }
+    impl Stream for FrameReader {
+        type Item = crate::Result<null::Frame>;
+        fn poll_next(self: Pin<&mut Self>, cx: &mut Context) -> Poll<Option<Self::Item>> {
+            let mut_self = self.get_mut();
+            loop {
+                match mut_self.connection.poll_event() {
+                    Some(Event::Frame(frame)) => return Poll::Ready(Some(Ok(frame))),
+                    Some(Event::Error(err)) => return Poll::Ready(Some(Err(err))),
+                    Some(_) => continue,
+                    None => (),
+                }
+                if mut_self.failed || mut_self.connection.is_closed() {
+                    return Poll::Ready(None);
+                }
+                match ready!(mut_self.poll_read(cx)) {
+                    Ok(0) => return Poll::Ready(None),
+                    Ok(_) => (),
+                    Err(err) => {
+                        mut_self.failed = true;
+                        return Poll::Ready(Some(Err(ConnectionError::from(err).into())));
+                    }
+                }
+            }
+        }
+    }
+
+    /// Frames sink over the sending half of a connection.
+    ///
+    /// The queued frames are sent with vectored writes.
+    pub struct FrameWriter {
+        half: Writer,
+        transmit: Transmit,
+    }
+    impl FrameWriter {
+        /// Maximal number of buffers given to a single vectored write.
+        const MAX_SLICES: usize = 64;
+        /// Number of queued messages from which sending waits for them to be written, like the
+        /// default `ZMQ_SNDHWM`.
+        pub const HIGH_WATER_MARK: usize = 1000;
+
+        fn new(half: Writer) -> Self {
+            Self {
+                half,
+                transmit: Transmit::default(),
+            }
+        }
+
+        /// Queue the frames of a message to be written on the next flush.
+        pub fn queue(&mut self, frames: impl IntoIterator<Item = null::Frame>) {
+            self.transmit.queue(frames);
+        }
+
+        /// Number of messages not fully written yet.
+        pub fn queued(&self) -> usize {
+            self.transmit.queued()
+        }
+
+        /// Send a frame right away.
+        pub async fn send(&mut self, frame: null::Frame) -> Result<(), ConnectionError> {
+            self.queue([frame]);
+            self.flush().await
+        }
+
+        /// Write every queued frame.
+        pub async fn flush(&mut self) -> Result<(), ConnectionError> {
+            futures::future::poll_fn(|cx| self.poll_flush(cx)).await
+        }
+
+        /// Shut the connection down, whatever is left in the queue.
+        pub async fn shutdown(&mut self) -> Result<(), ConnectionError> {
+            futures::future::poll_fn(|cx| self.poll_shutdown(cx)).await
+        }
+
+        /// Ready to queue a message while under the high water mark.
+        pub fn poll_ready(&mut self, cx: &mut Context) -> Poll<Result<(), ConnectionError>> {
+            if self.queued() < Self::HIGH_WATER_MARK {
+                Poll::Ready(Ok(()))
+            } else {
+                self.poll_flush(cx)
+            }
+        }
+
+        /// Write every queued frame.
+        pub fn poll_flush(&mut self, cx: &mut Context) -> Poll<Result<(), ConnectionError>> {
+            while !self.transmit.is_empty() {
+                let mut slices = [IoSlice::new(&[]); Self::MAX_SLICES];
+                let count = slices
+                    .iter_mut()
+                    .zip(self.transmit.chunks())
+                    .map(|(slice, chunk)| *slice = IoSlice::new(chunk))
+                    .count();
+                let written =
+                    ready!(Pin::new(&mut self.half).poll_write_vectored(cx, &slices[..count]))?;
+                if written == 0 {
+                    return Poll::Ready(Err(
+                        std::io::Error::from(std::io::ErrorKind::WriteZero).into()
+                    ));
+                }
+                self.transmit.advance(written);
+            }
+            Pin::new(&mut self.half).poll_flush(cx).map_err(Into::into)
+        }
+
+        /// Write every queued frame then shut the connection down.
+        pub fn poll_close(&mut self, cx: &mut Context) -> Poll<Result<(), ConnectionError>> {
+            ready!(self.poll_flush(cx))?;
+            self.poll_shutdown(cx)
+        }
+
+        /// Shut the connection down, whatever is left in the queue.
+        pub fn poll_shutdown(&mut self, cx: &mut Context) -> Poll<Result<(), ConnectionError>> {
+            Pin::new(&mut self.half).poll_close(cx).map_err(Into::into)
+        }
+    }
+}
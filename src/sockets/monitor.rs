@@ -0,0 +1,236 @@
+//! Connection lifecycle events of a socket, like `zmq_socket_monitor`.
+use crate::errors::ConnectionError;
+use crate::Error;
+
+use futures::channel::mpsc::{unbounded, UnboundedReceiver, UnboundedSender};
+use futures::Stream;
+use std::net::SocketAddr;
+use std::pin::Pin;
+use std::sync::{Arc, Mutex};
+use std::task::{Context, Poll};
+
+/// What happened to a connection.
+#[derive(Debug, Clone)]
+pub enum EventKind {
+    /// The TCP connection to the endpoint is established.
+    Connected,
+    /// The TCP connection to the endpoint is not established right away.
+    ConnectDelayed,
+    /// The TCP connection to the endpoint failed.
+    ConnectFailed(ConnectionError),
+    /// A new attempt to connect to the endpoint is made, after the reconnect interval.
+    ConnectRetried,
+    /// The socket is bound to the endpoint and listen for peers.
+    Listening,
+    /// A peer connection is accepted on the endpoint.
+    Accepted,
+    /// Accepting a peer connection on the endpoint failed.
+    AcceptFailed(ConnectionError),
+    /// The ZMTP handshake with the peer succeeded, messages can be exchanged.
+    HandshakeSucceeded,
+    /// The ZMTP handshake with the peer failed.
+    HandshakeFailed(ConnectionError),
+    /// The peer connection is lost, with the error which broke it, if any.
+    Disconnected(Option<Error>),
+    /// The socket is closed.
+    Closed,
+}
+
+/// An event occurring on a socket endpoint.
+#[derive(Debug, Clone)]
+pub struct Event {
+    /// The endpoint the socket connects or is bound to, as `tcp://host:port`.
+    pub endpoint: Arc<str>,
+    /// The address of the peer, once the TCP connection exists.
+    pub peer: Option<SocketAddr>,
+    pub kind: EventKind,
+}
+
+/// Publish the events of a socket to every subscribed [`Events`] stream.
+///
+/// A monitor is a cheap handle: clones publish to the same subscribers. Give one to
+/// [`Options::with_monitor`](super::Options::with_monitor) to observe the connection
+/// events, or get the socket's one from its `monitor()` method.
+#[derive(Debug, Clone, Default)]
+pub struct Monitor(Arc<Mutex<Vec<UnboundedSender<Event>>>>);
+
+impl Monitor {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Subscribe to the events published from now on.
+    pub fn events(&self) -> Events {
+        let (tx, rx) = unbounded();
+        self.0.lock().unwrap().push(tx);
+        Events(rx)
+    }
+
+    /// Publish an event, forgetting the dropped subscribers.
+    pub(crate) fn emit(&self, event: Event) {
+        self.0
+            .lock()
+            .unwrap()
+            .retain(|tx| tx.unbounded_send(event.clone()).is_ok());
+    }
+}
+
+/// The stream of events of a [`Monitor`].
+#[derive(Debug)]
+pub struct Events(UnboundedReceiver<Event>);
+
+impl Stream for Events {
+    type Item = Event;
+
+    fn poll_next(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Event>> {
+        Pin::new(&mut self.0).poll_next(cx)
+    }
+}
+
+/// A [`Monitor`] bound to an endpoint and its peer.
+#[derive(Debug, Clone)]
+pub(crate) struct Probe {
+    pub monitor: Monitor,
+    pub endpoint: Arc<str>,
+    pub peer: Option<SocketAddr>,
+}
+
+impl Probe {
+    pub fn new(monitor: Monitor, host: &str, port: u16) -> Self {
+        Self {
+            monitor,
+            endpoint: format!("tcp://{}:{}", host, port).into(),
+            peer: None,
+        }
+    }
+
+    pub fn emit(&self, kind: EventKind) {
+        self.monitor.emit(Event {
+            endpoint: self.endpoint.clone(),
+            peer: self.peer,
+            kind,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{EventKind, Events};
+    use crate::errors::{ConnectionError, ParseError};
+    use crate::packets::{Greeting, Mechanism};
+    use crate::sockets::{Options, Pull, Push};
+    use crate::Error;
+    use bytes::Bytes;
+    use futures::StreamExt;
+    use std::io::{Read, Write};
+    use std::net::TcpListener;
+    use std::time::Duration;
+
+    /// The next event, skipping `ConnectDelayed` as it depends on the operating system.
+    async fn next(events: &mut Events) -> EventKind {
+        loop {
+            match events.next().await.unwrap().kind {
+                EventKind::ConnectDelayed => continue,
+                kind => return kind,
+            }
+        }
+    }
+
+    /// A port nothing listens on.
+    fn closed_port() -> u16 {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        listener.local_addr().unwrap().port()
+    }
+
+    #[tokio::test]
+    async fn connected() {
+        let pull = Pull::new();
+        let addr = pull.bind("127.0.0.1", 0).await.unwrap();
+        let push = Push::new();
+        let mut events = push.monitor().events();
+        push.connect("127.0.0.1", addr.port()).await.unwrap();
+        let event = loop {
+            let event = events.next().await.unwrap();
+            if !matches!(event.kind, EventKind::ConnectDelayed) {
+                break event;
+            }
+        };
+        assert!(matches!(event.kind, EventKind::Connected));
+        assert_eq!(&*event.endpoint, format!("tcp://127.0.0.1:{}", addr.port()));
+        assert_eq!(event.peer, Some(addr));
+        assert!(matches!(
+            next(&mut events).await,
+            EventKind::HandshakeSucceeded
+        ));
+    }
+
+    #[tokio::test]
+    async fn refused() {
+        let port = closed_port();
+        let push = Push::new();
+        let mut events = push.monitor().events();
+        assert!(push.connect("127.0.0.1", port).await.is_err());
+        assert!(matches!(
+            next(&mut events).await,
+            EventKind::ConnectFailed(ConnectionError::UnaccessibleHost(_, _))
+        ));
+
+        let options = Options::default().with_reconnect_interval(Duration::from_millis(10));
+        let push = Push::with_options(options);
+        let mut events = push.monitor().events();
+        let connecting = tokio::spawn(async move { push.connect("127.0.0.1", port).await });
+        assert!(matches!(
+            next(&mut events).await,
+            EventKind::ConnectFailed(_)
+        ));
+        assert!(matches!(next(&mut events).await, EventKind::ConnectRetried));
+        assert!(matches!(
+            next(&mut events).await,
+            EventKind::ConnectFailed(_)
+        ));
+        connecting.abort();
+    }
+
+    #[tokio::test]
+    async fn handshake_failed() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let port = listener.local_addr().unwrap().port();
+        let peer = std::thread::spawn(move || {
+            let (mut stream, _) = listener.accept().unwrap();
+            let mut curve = [0; 20];
+            curve[..5].copy_from_slice(b"CURVE");
+            let greeting = Greeting::new().with_mechanism(Mechanism(curve));
+            stream.write_all(&greeting.to_bytes()).unwrap();
+            let _ = stream.read_to_end(&mut Vec::new());
+        });
+        let push = Push::new();
+        let mut events = push.monitor().events();
+        assert!(push.connect("127.0.0.1", port).await.is_err());
+        assert!(matches!(next(&mut events).await, EventKind::Connected));
+        assert!(matches!(
+            next(&mut events).await,
+            EventKind::HandshakeFailed(ConnectionError::MechanismMismatch())
+        ));
+        peer.join().unwrap();
+    }
+
+    #[tokio::test]
+    async fn disconnected_by_error() {
+        // a limit above the size of the READY command
+        let pull = Pull::with_options(Options::default().with_max_frame_size(30));
+        let addr = pull.bind("127.0.0.1", 0).await.unwrap();
+        let mut events = pull.monitor().events();
+        let push = Push::new();
+        push.connect("127.0.0.1", addr.port()).await.unwrap();
+        push.send(Bytes::from(vec![0; 40])).await.unwrap();
+        assert!(matches!(next(&mut events).await, EventKind::Accepted));
+        assert!(matches!(
+            next(&mut events).await,
+            EventKind::HandshakeSucceeded
+        ));
+        assert!(matches!(
+            next(&mut events).await,
+            EventKind::Disconnected(Some(Error::Parse(ParseError::FrameTooLarge(40, 30))))
+        ));
+    }
+}
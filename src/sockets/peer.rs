@@ -0,0 +1,126 @@
+//! The PEER socket type of the draft peer-to-peer pattern.
+use super::engine::{single, RoutingId, Sending};
+use super::SocketType;
+use crate::{Multipart, Result};
+
+use bytes::Bytes;
+use futures::{ready, Sink, Stream};
+use std::pin::Pin;
+use std::task::{Context, Poll};
+
+socket! {
+    /// A PEER socket, talking with many other PEER sockets.
+    ///
+    /// Each peer has a routing id: [`Peer::connect`] returns the one of the peer it connects to,
+    /// and every received message comes with the routing id of its sender. Messages are sent to
+    /// a peer by routing id. They are single-part, multipart messages sent by peers are dropped.
+    ///
+    /// # Exemple
+    ///
+    /// ```rust,no_run
+    /// use zmtp::sockets::Peer;
+    ///
+    /// # async fn run() -> zmtp::Result<()> {
+    /// let peer = Peer::new();
+    /// let routing_id = peer.connect("localhost", 55555).await?;
+    /// peer.send(routing_id, bytes::Bytes::from("Hi!")).await?;
+    /// let (from, reply) = peer.recv().await?;
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub struct Peer(peers: "PEER", connect -> RoutingId) {
+        sending: Sending,
+    }
+}
+
+impl Peer {
+    /// Send a single-part message to the peer with the `routing_id`.
+    ///
+    /// Wait for the peer queue to have room. Fail right away on multipart messages, and if
+    /// no connected peer has this routing id.
+    pub async fn send(&self, routing_id: RoutingId, msg: impl Into<Multipart>) -> Result<()> {
+        let msg = single(SocketType::Peer, msg.into())?;
+        self.engine.send_to(routing_id, msg.into()).await
+    }
+
+    /// Receive the next message from any peer, along with the peer routing id.
+    pub async fn recv(&self) -> Result<(RoutingId, Bytes)> {
+        loop {
+            let (routing_id, msg) = self.engine.recv().await;
+            if let Some(part) = msg.into_single() {
+                return Ok((routing_id, part));
+            }
+        }
+    }
+}
+
+impl Sink<(RoutingId, Bytes)> for Peer {
+    type Error = crate::Error;
+
+    fn poll_ready(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Result<()>> {
+        self.get_mut().sending.poll(cx)
+    }
+
+    fn start_send(self: Pin<&mut Self>, (routing_id, msg): (RoutingId, Bytes)) -> Result<()> {
+        let this = self.get_mut();
+        let send = this.engine.send_to(routing_id, msg.into());
+        this.sending.start(send);
+        Ok(())
+    }
+
+    fn poll_flush(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Result<()>> {
+        self.get_mut().sending.poll(cx)
+    }
+
+    fn poll_close(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Result<()>> {
+        self.get_mut().sending.poll(cx)
+    }
+}
+
+impl Stream for Peer {
+    type Item = Result<(RoutingId, Bytes)>;
+
+    fn poll_next(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        let this = self.get_mut();
+        loop {
+            let (routing_id, msg) = ready!(this.engine.poll_recv(cx));
+            if let Some(part) = msg.into_single() {
+                return Poll::Ready(Some(Ok((routing_id, part))));
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Peer;
+    use bytes::Bytes;
+
+    #[tokio::test]
+    async fn mesh() {
+        let hub = Peer::new();
+        let addr = hub.bind("127.0.0.1", 0).await.unwrap();
+        let (left, right) = (Peer::new(), Peer::new());
+        let left_hub = left.connect("127.0.0.1", addr.port()).await.unwrap();
+        let right_hub = right.connect("127.0.0.1", addr.port()).await.unwrap();
+
+        left.send(left_hub, Bytes::from("left")).await.unwrap();
+        let (left_id, msg) = hub.recv().await.unwrap();
+        assert_eq!(msg, "left");
+        right.send(right_hub, Bytes::from("right")).await.unwrap();
+        let (right_id, msg) = hub.recv().await.unwrap();
+        assert_eq!(msg, "right");
+        assert_ne!(left_id, right_id);
+
+        hub.send(right_id, Bytes::from("to right")).await.unwrap();
+        assert_eq!(
+            right.recv().await.unwrap(),
+            (right_hub, Bytes::from("to right"))
+        );
+        hub.send(left_id, Bytes::from("to left")).await.unwrap();
+        assert_eq!(
+            left.recv().await.unwrap(),
+            (left_hub, Bytes::from("to left"))
+        );
+    }
+}
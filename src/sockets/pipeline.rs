@@ -0,0 +1,107 @@
+//! The PUSH and PULL socket types of the pipeline pattern.
+use super::engine::Sending;
+use crate::{Multipart, Result};
+
+use futures::{ready, Sink, Stream};
+use std::pin::Pin;
+use std::task::{Context, Poll};
+
+socket! {
+    /// A PUSH socket, distributing messages to PULL sockets.
+    ///
+    /// Messages are sent to the connected pullers in turn.
+    pub struct Push(peers: "PULL") {
+        sending: Sending,
+    }
+}
+
+impl Push {
+    /// Send a message to the next puller in turn.
+    ///
+    /// Wait for a puller to be connected, and for its queue to have room.
+    pub async fn send(&self, msg: impl Into<Multipart>) -> Result<()> {
+        self.engine.send_round_robin(msg.into()).await
+    }
+}
+
+impl Sink<Multipart> for Push {
+    type Error = crate::Error;
+
+    fn poll_ready(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Result<()>> {
+        self.get_mut().sending.poll(cx)
+    }
+
+    fn start_send(self: Pin<&mut Self>, msg: Multipart) -> Result<()> {
+        let this = self.get_mut();
+        let send = this.engine.send_round_robin(msg);
+        this.sending.start(send);
+        Ok(())
+    }
+
+    fn poll_flush(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Result<()>> {
+        self.get_mut().sending.poll(cx)
+    }
+
+    fn poll_close(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Result<()>> {
+        self.get_mut().sending.poll(cx)
+    }
+}
+
+socket! {
+    /// A PULL socket, collecting the messages of PUSH sockets.
+    ///
+    /// Messages are received in the order they arrive, whichever pusher sent them.
+    pub struct Pull(peers: "PUSH") {}
+}
+
+impl Pull {
+    /// Receive the next message from any pusher.
+    pub async fn recv(&self) -> Result<Multipart> {
+        let (_, msg) = self.engine.recv().await;
+        Ok(msg)
+    }
+}
+
+impl Stream for Pull {
+    type Item = Result<Multipart>;
+
+    fn poll_next(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        let (_, msg) = ready!(self.get_mut().engine.poll_recv(cx));
+        Poll::Ready(Some(Ok(msg)))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Pull, Push};
+    use bytes::Bytes;
+
+    #[tokio::test]
+    async fn pipeline() {
+        let pull = Pull::new();
+        let addr = pull.bind("127.0.0.1", 0).await.unwrap();
+        let push = Push::new();
+        push.connect("127.0.0.1", addr.port()).await.unwrap();
+
+        for msg in ["a", "b"] {
+            push.send(Bytes::from(msg)).await.unwrap();
+        }
+        assert_eq!(pull.recv().await.unwrap(), Bytes::from("a").into());
+        assert_eq!(pull.recv().await.unwrap(), Bytes::from("b").into());
+    }
+
+    #[tokio::test]
+    async fn close() {
+        let pull = Pull::new();
+        let addr = pull.bind("127.0.0.1", 0).await.unwrap();
+        let push = Push::new();
+        push.connect("127.0.0.1", addr.port()).await.unwrap();
+
+        for msg in ["a", "b"] {
+            push.send(Bytes::from(msg)).await.unwrap();
+        }
+        assert_eq!(push.close().await, 0);
+        assert_eq!(pull.recv().await.unwrap(), Bytes::from("a").into());
+        assert_eq!(pull.recv().await.unwrap(), Bytes::from("b").into());
+    }
+}
@@ -0,0 +1,282 @@
+//! A steerable proxy between two sockets, like `zmq_proxy_steerable`.
+use crate::{Multipart, Result};
+
+use bytes::Bytes;
+use futures::{FutureExt, Sink, SinkExt, Stream, StreamExt};
+
+/// The messages and bytes going through one side of the proxy.
+#[derive(Debug, Default, Clone, Copy)]
+struct Traffic {
+    messages_in: u64,
+    bytes_in: u64,
+    messages_out: u64,
+    bytes_out: u64,
+}
+
+impl Traffic {
+    fn size(msg: &Multipart) -> u64 {
+        msg.0.iter().map(|part| part.len() as u64).sum()
+    }
+}
+
+/// The reply to the STATISTICS command: the frontend traffic, then the backend traffic.
+///
+/// Like libzmq, each counter is a frame holding a 64 bits integer in native byte order.
+fn statistics(frontend: Traffic, backend: Traffic) -> Multipart {
+    [frontend, backend]
+        .into_iter()
+        .flat_map(|t| [t.messages_in, t.bytes_in, t.messages_out, t.bytes_out])
+        .map(|counter| Bytes::copy_from_slice(&counter.to_ne_bytes()))
+        .collect::<Vec<_>>()
+        .into()
+}
+
+/// The sides of the proxy, indexing its traffic.
+const FRONTEND: usize = 0;
+const BACKEND: usize = 1;
+
+/// The state the control socket steers.
+struct Steering<K> {
+    control: Option<K>,
+    paused: bool,
+    traffic: [Traffic; 2],
+}
+
+impl<K> Steering<K>
+where
+    K: Stream<Item = Result<Multipart>> + Sink<Multipart, Error = crate::Error> + Unpin,
+{
+    /// The next command, or never if there is no control socket.
+    async fn command(&mut self) -> Option<Result<Multipart>> {
+        match &mut self.control {
+            Some(control) => control.next().await,
+            None => std::future::pending().await,
+        }
+    }
+
+    /// Obey a command and reply to it, returning whether the proxy keeps running.
+    ///
+    /// Like libzmq, every command gets a reply, as a REP control socket requires: the
+    /// statistics for `STATISTICS`, an empty frame otherwise.
+    async fn obey(&mut self, command: Option<Result<Multipart>>) -> Result<bool> {
+        let Some(command) = command else {
+            return Ok(false);
+        };
+        let mut running = true;
+        let reply = match command?.into_single().as_deref() {
+            Some(b"STATISTICS") => statistics(self.traffic[FRONTEND], self.traffic[BACKEND]),
+            command => {
+                match command {
+                    Some(b"PAUSE") => self.paused = true,
+                    Some(b"RESUME") => self.paused = false,
+                    Some(b"TERMINATE") => running = false,
+                    _ => (),
+                }
+                Bytes::new().into()
+            }
+        };
+        if let Some(control) = &mut self.control {
+            control.send(reply).await?;
+        }
+        Ok(running)
+    }
+}
+
+/// Send a message received on the `from` side to the other side, and to the capture socket,
+/// returning whether the proxy keeps running.
+///
+/// The commands are still obeyed while waiting for the other side to take the message.
+async fn forward<S, C, K>(
+    msg: Multipart,
+    from: usize,
+    to: &mut S,
+    capture: &mut Option<C>,
+    steering: &mut Steering<K>,
+) -> Result<bool>
+where
+    S: Sink<Multipart, Error = crate::Error> + Unpin,
+    C: Sink<Multipart, Error = crate::Error> + Unpin,
+    K: Stream<Item = Result<Multipart>> + Sink<Multipart, Error = crate::Error> + Unpin,
+{
+    let size = Traffic::size(&msg);
+    steering.traffic[from].messages_in += 1;
+    steering.traffic[from].bytes_in += size;
+    let sending = async {
+        if let Some(capture) = capture {
+            capture.send(msg.clone()).await?;
+        }
+        to.send(msg).await
+    };
+    let mut sending = std::pin::pin!(sending.fuse());
+    loop {
+        let command = futures::select! {
+            sent = sending => {
+                sent?;
+                break;
+            }
+            command = steering.command().fuse() => command,
+        };
+        if !steering.obey(command).await? {
+            return Ok(false);
+        }
+    }
+    let to_traffic = &mut steering.traffic[1 - from];
+    to_traffic.messages_out += 1;
+    to_traffic.bytes_out += size;
+    Ok(true)
+}
+
+/// The next message of a side, or never while the proxy is paused.
+async fn next<S>(side: &mut S, paused: bool) -> Option<Result<Multipart>>
+where
+    S: Stream<Item = Result<Multipart>> + Unpin,
+{
+    match paused {
+        false => side.next().await,
+        true => std::future::pending().await,
+    }
+}
+
+/// What woke the proxy up.
+enum Wakeup {
+    Frontend(Option<Result<Multipart>>),
+    Backend(Option<Result<Multipart>>),
+    Control(Option<Result<Multipart>>),
+}
+
+/// Shuttle messages between the `frontend` and the `backend` sockets, in both directions.
+///
+/// Every message is also sent to the `capture` socket, if any. The proxy obeys the commands
+/// received on the `control` socket, if any, and replies to each of them:
+/// - `PAUSE` stops shuttling messages, leaving them queued in the sockets,
+/// - `RESUME` starts shuttling them again,
+/// - `TERMINATE` stops the proxy,
+/// - `STATISTICS` replies with the number of messages and bytes received and sent by the
+///   frontend, then by the backend, each in a frame holding a 64 bits integer in native byte
+///   order.
+///
+/// The other commands are ignored, and get an empty reply like `PAUSE`, `RESUME` and
+/// `TERMINATE`. Return once terminated, or once a socket is closed.
+///
+/// # Exemple
+///
+/// ```rust,no_run
+/// use zmtp::sockets::{proxy, Dealer, Rep, Router};
+///
+/// # async fn run() -> zmtp::Result<()> {
+/// let (frontend, backend) = (Router::new(), Dealer::new());
+/// frontend.bind("0.0.0.0", 5559).await?;
+/// backend.bind("0.0.0.0", 5560).await?;
+/// proxy(frontend, backend, None::<Dealer>, None::<Rep>).await?;
+/// # Ok(())
+/// # }
+/// ```
+pub async fn proxy<F, B, C, K>(
+    frontend: F,
+    backend: B,
+    mut capture: Option<C>,
+    control: Option<K>,
+) -> Result<()>
+where
+    F: Stream<Item = Result<Multipart>> + Sink<Multipart, Error = crate::Error> + Unpin,
+    B: Stream<Item = Result<Multipart>> + Sink<Multipart, Error = crate::Error> + Unpin,
+    C: Sink<Multipart, Error = crate::Error> + Unpin,
+    K: Stream<Item = Result<Multipart>> + Sink<Multipart, Error = crate::Error> + Unpin,
+{
+    let (mut frontend_sink, mut frontend) = frontend.split();
+    let (mut backend_sink, mut backend) = backend.split();
+    let mut steering = Steering {
+        control,
+        paused: false,
+        traffic: Default::default(),
+    };
+    loop {
+        let paused = steering.paused;
+        let wakeup = futures::select! {
+            msg = next(&mut frontend, paused).fuse() => Wakeup::Frontend(msg),
+            msg = next(&mut backend, paused).fuse() => Wakeup::Backend(msg),
+            command = steering.command().fuse() => Wakeup::Control(command),
+        };
+        let running = match wakeup {
+            Wakeup::Frontend(None) | Wakeup::Backend(None) => false,
+            Wakeup::Frontend(Some(msg)) => {
+                let to = &mut backend_sink;
+                forward(msg?, FRONTEND, to, &mut capture, &mut steering).await?
+            }
+            Wakeup::Backend(Some(msg)) => {
+                let to = &mut frontend_sink;
+                forward(msg?, BACKEND, to, &mut capture, &mut steering).await?
+            }
+            Wakeup::Control(command) => steering.obey(command).await?,
+        };
+        if !running {
+            return Ok(());
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::proxy;
+    use crate::sockets::{Dealer, Rep, Router, Zmtp};
+    use crate::Multipart;
+    use bytes::Bytes;
+    use futures::{SinkExt, StreamExt};
+    use std::time::Duration;
+    use tokio::time::timeout;
+
+    #[tokio::test]
+    async fn queue() {
+        let (frontend, backend, control) = (Router::new(), Dealer::new(), Rep::new());
+        let front = frontend.bind("127.0.0.1", 0).await.unwrap();
+        let back = backend.bind("127.0.0.1", 0).await.unwrap();
+        let ctrl = control.bind("127.0.0.1", 0).await.unwrap();
+        let proxy = tokio::spawn(proxy(frontend, backend, None::<Dealer>, Some(control)));
+
+        let mut worker = Rep::new();
+        worker.connect("127.0.0.1", back.port()).await.unwrap();
+        let mut client = Zmtp::connect("127.0.0.1", front.port()).await.unwrap();
+        client.send(Bytes::from("ping").into()).await.unwrap();
+        assert_eq!(worker.recv().await.unwrap(), Bytes::from("ping").into());
+        worker.send(Bytes::from("pong")).await.unwrap();
+        assert_eq!(
+            client.next().await.unwrap().unwrap(),
+            Bytes::from("pong").into()
+        );
+
+        let mut controller = Zmtp::connect("127.0.0.1", ctrl.port()).await.unwrap();
+        let empty = Multipart::from(Bytes::new());
+        controller.send(Bytes::from("PAUSE").into()).await.unwrap();
+        assert_eq!(controller.next().await.unwrap().unwrap(), empty);
+        client.send(Bytes::from("ping").into()).await.unwrap();
+        let paused = timeout(Duration::from_millis(100), worker.recv()).await;
+        assert!(paused.is_err(), "the paused proxy forwarded a message");
+        controller.send(Bytes::from("RESUME").into()).await.unwrap();
+        assert_eq!(controller.next().await.unwrap().unwrap(), empty);
+        assert_eq!(worker.recv().await.unwrap(), Bytes::from("ping").into());
+        worker.send(Bytes::from("pong")).await.unwrap();
+        assert_eq!(
+            client.next().await.unwrap().unwrap(),
+            Bytes::from("pong").into()
+        );
+
+        controller
+            .send(Bytes::from("STATISTICS").into())
+            .await
+            .unwrap();
+        let statistics = controller.next().await.unwrap().unwrap();
+        let counters: Vec<_> = statistics
+            .0
+            .iter()
+            .map(|c| u64::from_ne_bytes(c[..].try_into().unwrap()))
+            .collect();
+        // the ROUTER frontend adds a routing id frame of 5 bytes to the 4 bytes of the body
+        assert_eq!(counters, [2, 18, 2, 18, 2, 18, 2, 18]);
+        controller
+            .send(Bytes::from("TERMINATE").into())
+            .await
+            .unwrap();
+        assert_eq!(controller.next().await.unwrap().unwrap(), empty);
+        proxy.await.unwrap().unwrap();
+    }
+}
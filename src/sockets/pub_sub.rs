@@ -0,0 +1,288 @@
+//! The PUB, XPUB, SUB and XSUB socket types of the publish-subscribe pattern.
+//!
+//! Subscriptions are ZMTP 3.0 messages: a single frame, the topic prefixed by `1` to subscribe
+//! or by `0` to cancel.
+use super::engine::{parse_subscription, Engine};
+use crate::{Multipart, Result};
+
+use futures::{ready, Sink, Stream};
+use std::pin::Pin;
+use std::task::{Context, Poll};
+
+/// Publish a message to the peers subscribed to a prefix of its first frame.
+fn publish(engine: &Engine, msg: Multipart) {
+    let first = msg.0.first().cloned().unwrap_or_default();
+    engine.broadcast(&first, msg);
+}
+
+socket! {
+    /// A PUB socket, publishing messages to SUB and XSUB sockets.
+    ///
+    /// Each message is sent to the peers subscribed to a prefix of its first frame, and dropped
+    /// for the peers whose queue is full.
+    pub struct Pub(peers: "SUB or XSUB") {}
+}
+
+impl Pub {
+    /// Publish a message to the subscribed peers, never waiting.
+    pub fn send(&self, msg: impl Into<Multipart>) {
+        publish(&self.engine, msg.into());
+    }
+}
+
+impl Sink<Multipart> for Pub {
+    type Error = crate::Error;
+
+    fn poll_ready(self: Pin<&mut Self>, _: &mut Context<'_>) -> Poll<Result<()>> {
+        Poll::Ready(Ok(()))
+    }
+
+    fn start_send(self: Pin<&mut Self>, msg: Multipart) -> Result<()> {
+        publish(&self.engine, msg);
+        Ok(())
+    }
+
+    fn poll_flush(self: Pin<&mut Self>, _: &mut Context<'_>) -> Poll<Result<()>> {
+        Poll::Ready(Ok(()))
+    }
+
+    fn poll_close(self: Pin<&mut Self>, _: &mut Context<'_>) -> Poll<Result<()>> {
+        Poll::Ready(Ok(()))
+    }
+}
+
+socket! {
+    /// An XPUB socket, a PUB socket receiving the subscriptions of its peers.
+    ///
+    /// A subscription is received when a peer is the first to subscribe to its topic, and a
+    /// cancellation when the last subscribed peer cancels.
+    pub struct XPub(peers: "SUB or XSUB") {}
+}
+
+impl XPub {
+    /// Publish a message to the subscribed peers, never waiting.
+    pub fn send(&self, msg: impl Into<Multipart>) {
+        publish(&self.engine, msg.into());
+    }
+
+    /// Receive the next subscription or cancellation of the peers.
+    pub async fn recv(&self) -> Result<Multipart> {
+        let (_, msg) = self.engine.recv().await;
+        Ok(msg)
+    }
+}
+
+impl Sink<Multipart> for XPub {
+    type Error = crate::Error;
+
+    fn poll_ready(self: Pin<&mut Self>, _: &mut Context<'_>) -> Poll<Result<()>> {
+        Poll::Ready(Ok(()))
+    }
+
+    fn start_send(self: Pin<&mut Self>, msg: Multipart) -> Result<()> {
+        publish(&self.engine, msg);
+        Ok(())
+    }
+
+    fn poll_flush(self: Pin<&mut Self>, _: &mut Context<'_>) -> Poll<Result<()>> {
+        Poll::Ready(Ok(()))
+    }
+
+    fn poll_close(self: Pin<&mut Self>, _: &mut Context<'_>) -> Poll<Result<()>> {
+        Poll::Ready(Ok(()))
+    }
+}
+
+impl Stream for XPub {
+    type Item = Result<Multipart>;
+
+    fn poll_next(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        let (_, msg) = ready!(self.get_mut().engine.poll_recv(cx));
+        Poll::Ready(Some(Ok(msg)))
+    }
+}
+
+/// Whether a received message matches a subscription of the socket.
+fn matches(engine: &Engine, msg: &Multipart) -> bool {
+    engine.matches(msg.0.first().map_or(&[], |part| part))
+}
+
+socket! {
+    /// A SUB socket, receiving the messages of PUB and XPUB sockets.
+    ///
+    /// Only the messages whose first frame starts with a subscribed topic are received: the
+    /// subscriptions are sent to the publishers, and checked again on reception.
+    pub struct Sub(peers: "PUB or XPUB") {}
+}
+
+impl Sub {
+    /// Receive the messages starting with `topic`, the empty topic matching every message.
+    pub fn subscribe(&self, topic: impl AsRef<[u8]>) {
+        self.engine.subscribe(topic.as_ref());
+    }
+
+    /// Stop receiving the messages of a subscribed `topic`.
+    pub fn unsubscribe(&self, topic: impl AsRef<[u8]>) {
+        self.engine.unsubscribe(topic.as_ref());
+    }
+
+    /// Receive the next message matching a subscription.
+    pub async fn recv(&self) -> Result<Multipart> {
+        loop {
+            let (_, msg) = self.engine.recv().await;
+            if matches(&self.engine, &msg) {
+                return Ok(msg);
+            }
+        }
+    }
+}
+
+impl Stream for Sub {
+    type Item = Result<Multipart>;
+
+    fn poll_next(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        let this = self.get_mut();
+        loop {
+            let (_, msg) = ready!(this.engine.poll_recv(cx));
+            if matches(&this.engine, &msg) {
+                return Poll::Ready(Some(Ok(msg)));
+            }
+        }
+    }
+}
+
+socket! {
+    /// An XSUB socket, a SUB socket subscribing by sending subscription messages.
+    ///
+    /// Sent messages which are not subscriptions are dropped.
+    pub struct XSub(peers: "PUB or XPUB") {}
+}
+
+impl XSub {
+    /// Send a subscription or a cancellation to the current and future publishers.
+    pub fn send(&self, msg: impl Into<Multipart>) {
+        self.subscription(msg.into());
+    }
+
+    /// Receive the next message matching a subscription.
+    pub async fn recv(&self) -> Result<Multipart> {
+        loop {
+            let (_, msg) = self.engine.recv().await;
+            if matches(&self.engine, &msg) {
+                return Ok(msg);
+            }
+        }
+    }
+
+    fn subscription(&self, msg: Multipart) {
+        match parse_subscription(&msg) {
+            Some((topic, true)) => self.engine.subscribe(topic),
+            Some((topic, false)) => self.engine.unsubscribe(topic),
+            None => (),
+        }
+    }
+}
+
+impl Sink<Multipart> for XSub {
+    type Error = crate::Error;
+
+    fn poll_ready(self: Pin<&mut Self>, _: &mut Context<'_>) -> Poll<Result<()>> {
+        Poll::Ready(Ok(()))
+    }
+
+    fn start_send(self: Pin<&mut Self>, msg: Multipart) -> Result<()> {
+        self.subscription(msg);
+        Ok(())
+    }
+
+    fn poll_flush(self: Pin<&mut Self>, _: &mut Context<'_>) -> Poll<Result<()>> {
+        Poll::Ready(Ok(()))
+    }
+
+    fn poll_close(self: Pin<&mut Self>, _: &mut Context<'_>) -> Poll<Result<()>> {
+        Poll::Ready(Ok(()))
+    }
+}
+
+impl Stream for XSub {
+    type Item = Result<Multipart>;
+
+    fn poll_next(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        let this = self.get_mut();
+        loop {
+            let (_, msg) = ready!(this.engine.poll_recv(cx));
+            if matches(&this.engine, &msg) {
+                return Poll::Ready(Some(Ok(msg)));
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Sub, XPub, XSub};
+    use crate::packets::null;
+    use crate::runtime::Listener;
+    use crate::sockets::monitor::Probe;
+    use crate::sockets::{handshake, states, Monitor, Options, SocketType};
+    use crate::Multipart;
+    use bytes::Bytes;
+    use futures::StreamExt;
+    use std::time::Duration;
+
+    #[tokio::test]
+    async fn forwarder() {
+        // an XPUB publisher, to know when the forwarded subscription reaches it
+        let publisher = XPub::new();
+        let addr = publisher.bind("127.0.0.1", 0).await.unwrap();
+        let (xsub, xpub) = (XSub::new(), XPub::new());
+        xsub.connect("127.0.0.1", addr.port()).await.unwrap();
+        let addr = xpub.bind("127.0.0.1", 0).await.unwrap();
+        let sub = Sub::new();
+        sub.subscribe("news");
+        sub.connect("127.0.0.1", addr.port()).await.unwrap();
+
+        let subscription = xpub.recv().await.unwrap();
+        assert_eq!(subscription, Bytes::from("\x01news").into());
+        xsub.send(subscription.clone());
+        assert_eq!(publisher.recv().await.unwrap(), subscription);
+        publisher.send(Bytes::from("sport"));
+        publisher.send(Multipart(vec![Bytes::from("news"), Bytes::from("hi")]));
+        let msg = xsub.recv().await.unwrap();
+        assert_eq!(msg.0[0], "news");
+        xpub.send(msg);
+        assert_eq!(sub.recv().await.unwrap().0[1], "hi");
+    }
+
+    #[tokio::test]
+    async fn subscriptions_to_a_stalled_peer() {
+        // a publisher not reading until the subscriptions are made
+        let listener = Listener::bind("127.0.0.1", 0).await.unwrap();
+        let port = listener.local_addr().unwrap().port();
+        let publisher = tokio::spawn(async move {
+            let connected = states::Connected::accept(&listener).await.unwrap();
+            let probe = Probe::new(Monitor::new(), "127.0.0.1", port);
+            handshake(connected, SocketType::Pub, &Options::default(), &probe)
+                .await
+                .unwrap()
+        });
+        let sub = Sub::new();
+        sub.connect("127.0.0.1", port).await.unwrap();
+        let mut publisher = publisher.await.unwrap();
+
+        // more than the queue of the peer holds
+        let count = 2 * states::FrameWriter::HIGH_WATER_MARK;
+        for i in 0..count {
+            sub.subscribe(format!("{i:0>1000}"));
+        }
+        let received = async {
+            for i in 0..count {
+                let frame = publisher.reader.next().await.unwrap().unwrap();
+                let topic = format!("\x01{i:0>1000}");
+                assert!(matches!(frame, null::Frame::Message(msg) if msg == topic));
+            }
+        };
+        let within = tokio::time::timeout(Duration::from_secs(10), received);
+        within.await.expect("subscriptions were dropped");
+    }
+}
@@ -0,0 +1,177 @@
+//! The RADIO and DISH socket types of the draft group messaging pattern (RFC 48).
+//!
+//! Over TCP, a message is sent as two frames: the group name, then the single-part body.
+use super::engine::single;
+use super::SocketType;
+use crate::errors::SocketError;
+use crate::{Multipart, Result};
+
+use bytes::Bytes;
+use futures::{ready, Sink, Stream};
+use std::pin::Pin;
+use std::task::{Context, Poll};
+
+/// Maximal length of a group name, like `ZMQ_GROUP_MAX_LENGTH`.
+pub const MAX_GROUP_LEN: usize = 255;
+
+/// Fail on group names longer than [`MAX_GROUP_LEN`].
+fn check_group(group: &str) -> Result<()> {
+    if group.len() > MAX_GROUP_LEN {
+        return Err(SocketError::GroupTooLong(group.to_string()).into());
+    }
+    Ok(())
+}
+
+socket! {
+    /// A RADIO socket, publishing messages to DISH sockets by group.
+    ///
+    /// Each single-part message is sent with a group, to the dishes which joined this group.
+    /// Like a PUB socket, sending never waits: a dish whose queue is full misses the message.
+    pub struct Radio(peers: "DISH") {}
+}
+
+impl Radio {
+    /// Send a single-part message to the dishes which joined `group`.
+    ///
+    /// Fail on multipart messages, and on group names longer than [`MAX_GROUP_LEN`].
+    pub async fn send(&self, group: &str, msg: impl Into<Multipart>) -> Result<()> {
+        let body = single(SocketType::Radio, msg.into())?;
+        self.publish(group, body)
+    }
+
+    fn publish(&self, group: &str, body: Bytes) -> Result<()> {
+        check_group(group)?;
+        let name = Bytes::copy_from_slice(group.as_bytes());
+        self.engine
+            .publish(group.as_bytes(), Multipart(vec![name, body]));
+        Ok(())
+    }
+}
+
+impl Sink<(String, Bytes)> for Radio {
+    type Error = crate::Error;
+
+    fn poll_ready(self: Pin<&mut Self>, _: &mut Context<'_>) -> Poll<Result<()>> {
+        Poll::Ready(Ok(()))
+    }
+
+    fn start_send(self: Pin<&mut Self>, (group, body): (String, Bytes)) -> Result<()> {
+        self.publish(&group, body)
+    }
+
+    fn poll_flush(self: Pin<&mut Self>, _: &mut Context<'_>) -> Poll<Result<()>> {
+        Poll::Ready(Ok(()))
+    }
+
+    fn poll_close(self: Pin<&mut Self>, _: &mut Context<'_>) -> Poll<Result<()>> {
+        Poll::Ready(Ok(()))
+    }
+}
+
+socket! {
+    /// A DISH socket, receiving the messages RADIO sockets publish to the groups it joined.
+    ///
+    /// Each message is received along with its group. Messages of other groups are dropped.
+    ///
+    /// # Exemple
+    ///
+    /// ```rust,no_run
+    /// use zmtp::sockets::Dish;
+    ///
+    /// # async fn run() -> zmtp::Result<()> {
+    /// let dish = Dish::new();
+    /// dish.connect("localhost", 55555).await?;
+    /// dish.join("weather")?;
+    /// let (group, forecast) = dish.recv().await?;
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub struct Dish(peers: "RADIO") {}
+}
+
+impl Dish {
+    /// Receive the messages published to `group`, from the radios connected now and later.
+    ///
+    /// Fail on group names longer than [`MAX_GROUP_LEN`].
+    pub fn join(&self, group: &str) -> Result<()> {
+        check_group(group)?;
+        self.engine.subscribe(group.as_bytes());
+        Ok(())
+    }
+
+    /// Stop receiving the messages published to `group`.
+    pub fn leave(&self, group: &str) -> Result<()> {
+        check_group(group)?;
+        self.engine.unsubscribe(group.as_bytes());
+        Ok(())
+    }
+
+    /// Receive the next message of a joined group, along with its group.
+    pub async fn recv(&self) -> Result<(String, Bytes)> {
+        loop {
+            let (_, msg) = self.engine.recv().await;
+            if let Some(msg) = self.accept(msg) {
+                return Ok(msg);
+            }
+        }
+    }
+
+    /// The group and body of a received message, unless it is not from a joined group.
+    fn accept(&self, msg: Multipart) -> Option<(String, Bytes)> {
+        match <[Bytes; 2]>::try_from(msg.0) {
+            Ok([group, body]) if self.engine.is_subscribed(&group) => {
+                Some((String::from_utf8_lossy(&group).into_owned(), body))
+            }
+            _ => None,
+        }
+    }
+}
+
+impl Stream for Dish {
+    type Item = Result<(String, Bytes)>;
+
+    fn poll_next(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        let this = self.get_mut();
+        loop {
+            let (_, msg) = ready!(this.engine.poll_recv(cx));
+            if let Some(msg) = this.accept(msg) {
+                return Poll::Ready(Some(Ok(msg)));
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Dish, Radio};
+    use bytes::Bytes;
+
+    #[tokio::test]
+    async fn groups() {
+        let dish = Dish::new();
+        let addr = dish.bind("127.0.0.1", 0).await.unwrap();
+        dish.join("weather").unwrap();
+        let radio = Radio::new();
+        radio.connect("127.0.0.1", addr.port()).await.unwrap();
+        // wait for the JOIN command to reach the radio
+        radio.engine.member(b"weather", true).await;
+
+        radio.send("sports", Bytes::from("goal")).await.unwrap();
+        radio.send("weather", Bytes::from("rain")).await.unwrap();
+        assert_eq!(
+            dish.recv().await.unwrap(),
+            ("weather".to_string(), Bytes::from("rain"))
+        );
+
+        dish.leave("weather").unwrap();
+        dish.join("sports").unwrap();
+        radio.engine.member(b"sports", true).await;
+        radio.send("weather", Bytes::from("sun")).await.unwrap();
+        radio.send("sports", Bytes::from("miss")).await.unwrap();
+        assert_eq!(
+            dish.recv().await.unwrap(),
+            ("sports".to_string(), Bytes::from("miss"))
+        );
+        assert!(dish.join(&"x".repeat(256)).is_err());
+    }
+}
@@ -0,0 +1,374 @@
+//! The REP, DEALER and ROUTER socket types of the request-reply pattern.
+//!
+//! The REQ socket is the [`Zmtp`](super::Zmtp) socket.
+use super::engine::{RoutingId, Sending};
+use crate::errors::SocketError;
+use crate::{Multipart, Result};
+
+use bytes::Bytes;
+use futures::{ready, Future, Sink, Stream};
+use std::pin::Pin;
+use std::task::{Context, Poll};
+
+socket! {
+    /// A REP socket, replying to REQ and DEALER sockets.
+    ///
+    /// Each received request must be replied to before the next one is received: the reply is
+    /// sent to the peer of the request, with the envelope of the request.
+    pub struct Rep(peers: "REQ or DEALER") {
+        /// The peer and the envelope of the request to reply to.
+        request: Option<(RoutingId, Vec<Bytes>)>,
+        sending: Sending,
+    }
+}
+
+impl Rep {
+    /// Receive the next request from any peer, without its envelope.
+    ///
+    /// A request not replied to yet is given up.
+    pub async fn recv(&mut self) -> Result<Multipart> {
+        loop {
+            let (id, msg) = self.engine.recv().await;
+            if let Some(body) = self.accept(id, msg) {
+                return Ok(body);
+            }
+        }
+    }
+
+    /// Send the reply to the last received request.
+    ///
+    /// Fail if no request was received since the last reply. The reply is dropped if the
+    /// peer of the request is gone.
+    pub async fn send(&mut self, msg: impl Into<Multipart>) -> Result<()> {
+        self.reply(msg.into())?.await
+    }
+
+    /// Keep the envelope of a request, returning its body.
+    ///
+    /// Requests without an envelope delimiter are dropped.
+    fn accept(&mut self, id: RoutingId, msg: Multipart) -> Option<Multipart> {
+        let mut envelope = msg.0;
+        let delimiter = envelope.iter().position(|part| part.is_empty())?;
+        let body = envelope.split_off(delimiter + 1);
+        self.request = Some((id, envelope));
+        Some(Multipart(body))
+    }
+
+    fn reply(&mut self, msg: Multipart) -> Result<impl Future<Output = Result<()>>> {
+        let (id, mut parts) = self.request.take().ok_or(SocketError::NoRequest)?;
+        parts.extend(msg.0);
+        let send = self.engine.send_to(id, Multipart(parts));
+        Ok(async move {
+            match send.await {
+                Err(crate::Error::Socket(SocketError::UnknownRoutingId(_))) => Ok(()),
+                sent => sent,
+            }
+        })
+    }
+}
+
+impl Sink<Multipart> for Rep {
+    type Error = crate::Error;
+
+    fn poll_ready(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Result<()>> {
+        self.get_mut().sending.poll(cx)
+    }
+
+    fn start_send(self: Pin<&mut Self>, msg: Multipart) -> Result<()> {
+        let this = self.get_mut();
+        let send = this.reply(msg)?;
+        this.sending.start(send);
+        Ok(())
+    }
+
+    fn poll_flush(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Result<()>> {
+        self.get_mut().sending.poll(cx)
+    }
+
+    fn poll_close(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Result<()>> {
+        self.get_mut().sending.poll(cx)
+    }
+}
+
+impl Stream for Rep {
+    type Item = Result<Multipart>;
+
+    fn poll_next(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        let this = self.get_mut();
+        loop {
+            let (id, msg) = ready!(this.engine.poll_recv(cx));
+            if let Some(body) = this.accept(id, msg) {
+                return Poll::Ready(Some(Ok(body)));
+            }
+        }
+    }
+}
+
+/// The frame identifying the peer `id` in the messages of a ROUTER socket.
+///
+/// Like the routing ids libzmq generates: a zero byte, then a 32 bits integer.
+fn routing_frame(id: RoutingId) -> Bytes {
+    let mut frame = vec![0];
+    frame.extend(id.to_be_bytes());
+    frame.into()
+}
+
+/// The peer identified by the first frame of a message sent on a ROUTER socket.
+fn routing_id(frame: &[u8]) -> Option<RoutingId> {
+    match frame {
+        [0, id @ ..] => Some(RoutingId::from_be_bytes(id.try_into().ok()?)),
+        _ => None,
+    }
+}
+
+socket! {
+    /// A ROUTER socket, talking with REQ, DEALER and ROUTER sockets.
+    ///
+    /// Each received message is prefixed by a frame identifying its peer, and each sent message
+    /// starts with the frame of the peer to send it to. Messages to unknown peers are dropped.
+    pub struct Router(peers: "REQ, DEALER or ROUTER") {
+        sending: Sending,
+    }
+}
+
+impl Router {
+    /// Send a message to the peer identified by its first frame.
+    pub async fn send(&self, msg: impl Into<Multipart>) -> Result<()> {
+        self.route(msg.into()).await
+    }
+
+    /// Receive the next message from any peer, prefixed by the frame identifying the peer.
+    pub async fn recv(&self) -> Result<Multipart> {
+        let (id, msg) = self.engine.recv().await;
+        Ok(Self::identify(id, msg))
+    }
+
+    fn identify(id: RoutingId, msg: Multipart) -> Multipart {
+        let mut parts = msg.0;
+        parts.insert(0, routing_frame(id));
+        Multipart(parts)
+    }
+
+    fn route(&self, msg: Multipart) -> impl Future<Output = Result<()>> {
+        let mut parts = msg.0.into_iter();
+        let id = parts.next().as_deref().and_then(routing_id);
+        let send = id.map(|id| self.engine.send_to(id, parts.collect()));
+        async move {
+            match send {
+                Some(send) => match send.await {
+                    Err(crate::Error::Socket(SocketError::UnknownRoutingId(_))) => Ok(()),
+                    sent => sent,
+                },
+                None => Ok(()),
+            }
+        }
+    }
+}
+
+impl Sink<Multipart> for Router {
+    type Error = crate::Error;
+
+    fn poll_ready(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Result<()>> {
+        self.get_mut().sending.poll(cx)
+    }
+
+    fn start_send(self: Pin<&mut Self>, msg: Multipart) -> Result<()> {
+        let this = self.get_mut();
+        let send = this.route(msg);
+        this.sending.start(send);
+        Ok(())
+    }
+
+    fn poll_flush(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Result<()>> {
+        self.get_mut().sending.poll(cx)
+    }
+
+    fn poll_close(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Result<()>> {
+        self.get_mut().sending.poll(cx)
+    }
+}
+
+impl Stream for Router {
+    type Item = Result<Multipart>;
+
+    fn poll_next(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        let (id, msg) = ready!(self.get_mut().engine.poll_recv(cx));
+        Poll::Ready(Some(Ok(Self::identify(id, msg))))
+    }
+}
+
+socket! {
+    /// A DEALER socket, talking with REP, DEALER and ROUTER sockets.
+    ///
+    /// Messages are sent to the connected peers in turn, and received from all of them.
+    pub struct Dealer(peers: "REP, DEALER or ROUTER") {
+        sending: Sending,
+    }
+}
+
+impl Dealer {
+    /// Send a message to the next peer in turn.
+    ///
+    /// Wait for a peer to be connected, and for its queue to have room.
+    pub async fn send(&self, msg: impl Into<Multipart>) -> Result<()> {
+        self.engine.send_round_robin(msg.into()).await
+    }
+
+    /// Receive the next message from any peer.
+    pub async fn recv(&self) -> Result<Multipart> {
+        let (_, msg) = self.engine.recv().await;
+        Ok(msg)
+    }
+}
+
+impl Sink<Multipart> for Dealer {
+    type Error = crate::Error;
+
+    fn poll_ready(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Result<()>> {
+        self.get_mut().sending.poll(cx)
+    }
+
+    fn start_send(self: Pin<&mut Self>, msg: Multipart) -> Result<()> {
+        let this = self.get_mut();
+        let send = this.engine.send_round_robin(msg);
+        this.sending.start(send);
+        Ok(())
+    }
+
+    fn poll_flush(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Result<()>> {
+        self.get_mut().sending.poll(cx)
+    }
+
+    fn poll_close(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Result<()>> {
+        self.get_mut().sending.poll(cx)
+    }
+}
+
+impl Stream for Dealer {
+    type Item = Result<Multipart>;
+
+    fn poll_next(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        let (_, msg) = ready!(self.get_mut().engine.poll_recv(cx));
+        Poll::Ready(Some(Ok(msg)))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Rep, Router};
+    use crate::errors::ParseError;
+    use crate::packets::null;
+    use crate::runtime::Listener;
+    use crate::sockets::monitor::Probe;
+    use crate::sockets::{handshake, states, Monitor, Options, SocketType, Zmtp};
+    use crate::{Error, Multipart};
+    use bytes::Bytes;
+    use futures::{SinkExt, StreamExt};
+    use std::time::{Duration, Instant};
+
+    #[tokio::test]
+    async fn req_rep() {
+        let mut rep = Rep::new();
+        let addr = rep.bind("127.0.0.1", 0).await.unwrap();
+        let mut req = Zmtp::connect("127.0.0.1", addr.port()).await.unwrap();
+
+        req.send(Bytes::from("ping").into()).await.unwrap();
+        assert_eq!(rep.recv().await.unwrap(), Bytes::from("ping").into());
+        rep.send(Bytes::from("pong")).await.unwrap();
+        assert_eq!(
+            req.next().await.unwrap().unwrap(),
+            Bytes::from("pong").into()
+        );
+        assert!(rep.send(Bytes::from("pong")).await.is_err());
+    }
+
+    #[tokio::test]
+    async fn router_envelope() {
+        let router = Router::new();
+        let addr = router.bind("127.0.0.1", 0).await.unwrap();
+        let mut req = Zmtp::connect("127.0.0.1", addr.port()).await.unwrap();
+
+        req.send(Bytes::from("ping").into()).await.unwrap();
+        let request = router.recv().await.unwrap();
+        assert_eq!(request.0[1..], [Bytes::new(), Bytes::from("ping")]);
+        let mut reply = request.0;
+        reply[2] = Bytes::from("pong");
+        router.send(Multipart(reply)).await.unwrap();
+        assert_eq!(
+            req.next().await.unwrap().unwrap(),
+            Bytes::from("pong").into()
+        );
+    }
+
+    #[tokio::test]
+    async fn reply_without_delimiter() {
+        let router = Router::new();
+        let addr = router.bind("127.0.0.1", 0).await.unwrap();
+        let mut req = Zmtp::connect("127.0.0.1", addr.port()).await.unwrap();
+
+        let replying = tokio::spawn(async move {
+            let request = router.recv().await.unwrap();
+            let id = request.0[0].clone();
+            router
+                .send(Multipart(vec![id, Bytes::from("pong")]))
+                .await
+                .unwrap();
+            router
+        });
+        let reply = req.send_frame(null::Frame::Message(Bytes::from("ping")));
+        assert!(matches!(
+            reply.await,
+            Err(Error::Parse(ParseError::UnexpectedFrame(_)))
+        ));
+        replying.await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn close_flushing() {
+        let router = Router::new();
+        let addr = router.bind("127.0.0.1", 0).await.unwrap();
+        let options = Options::default().with_linger(Duration::from_secs(1));
+        let req = Zmtp::connect_with("127.0.0.1", addr.port(), options)
+            .await
+            .unwrap();
+
+        let (mut sender, _receiver) = req.split();
+        for msg in ["a", "b", "c"] {
+            sender.feed(Bytes::from(msg).into()).await.unwrap();
+        }
+        assert_eq!(sender.close().await.unwrap(), 0);
+        for msg in ["a", "b", "c"] {
+            assert_eq!(router.recv().await.unwrap().0[2], Bytes::from(msg));
+        }
+    }
+
+    #[tokio::test]
+    async fn close_timing_out() {
+        // a peer never reading once the handshake is done
+        let listener = Listener::bind("127.0.0.1", 0).await.unwrap();
+        let port = listener.local_addr().unwrap().port();
+        let peer = tokio::spawn(async move {
+            let connected = states::Connected::accept(&listener).await.unwrap();
+            let probe = Probe::new(Monitor::new(), "127.0.0.1", port);
+            handshake(connected, SocketType::Router, &Options::default(), &probe)
+                .await
+                .unwrap()
+        });
+        let linger = Duration::from_millis(100);
+        let options = Options::default().with_linger(linger);
+        let mut req = Zmtp::connect_with("127.0.0.1", port, options)
+            .await
+            .unwrap();
+        let _peer = peer.await.unwrap();
+
+        // far more than the socket buffers hold
+        let body = Bytes::from(vec![0; 1 << 20]);
+        for _ in 0..64 {
+            req.feed(body.clone().into()).await.unwrap();
+        }
+        let start = Instant::now();
+        let discarded = req.close().await.unwrap();
+        assert!(start.elapsed() >= linger);
+        assert!((1..=64).contains(&discarded), "{discarded} discarded");
+    }
+}
@@ -0,0 +1,139 @@
+//! The SCATTER and GATHER socket types of the draft pipeline pattern.
+use super::engine::{single, Sending};
+use super::SocketType;
+use crate::{Multipart, Result};
+
+use bytes::Bytes;
+use futures::{ready, Sink, Stream};
+use std::pin::Pin;
+use std::task::{Context, Poll};
+
+socket! {
+    /// A SCATTER socket, distributing messages to GATHER sockets.
+    ///
+    /// Like a PUSH socket, its single-part messages are sent to the connected gathers in turn.
+    pub struct Scatter(peers: "GATHER") {
+        sending: Sending,
+    }
+}
+
+impl Scatter {
+    /// Send a single-part message to the next gather in turn.
+    ///
+    /// Wait for a gather to be connected, and for its queue to have room. Fail right away
+    /// on multipart messages.
+    pub async fn send(&self, msg: impl Into<Multipart>) -> Result<()> {
+        let msg = single(SocketType::Scatter, msg.into())?;
+        self.engine.send_round_robin(msg.into()).await
+    }
+}
+
+impl Sink<Bytes> for Scatter {
+    type Error = crate::Error;
+
+    fn poll_ready(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Result<()>> {
+        self.get_mut().sending.poll(cx)
+    }
+
+    fn start_send(self: Pin<&mut Self>, msg: Bytes) -> Result<()> {
+        let this = self.get_mut();
+        let send = this.engine.send_round_robin(msg.into());
+        this.sending.start(send);
+        Ok(())
+    }
+
+    fn poll_flush(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Result<()>> {
+        self.get_mut().sending.poll(cx)
+    }
+
+    fn poll_close(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Result<()>> {
+        self.get_mut().sending.poll(cx)
+    }
+}
+
+socket! {
+    /// A GATHER socket, collecting the messages of SCATTER sockets.
+    ///
+    /// Like a PULL socket, it receives the single-part messages of all the connected scatters, in
+    /// the order they arrive. Multipart messages are dropped.
+    pub struct Gather(peers: "SCATTER") {}
+}
+
+impl Gather {
+    /// Receive the next message from any scatter.
+    pub async fn recv(&self) -> Result<Bytes> {
+        loop {
+            let (_, msg) = self.engine.recv().await;
+            if let Some(part) = msg.into_single() {
+                return Ok(part);
+            }
+        }
+    }
+}
+
+impl Stream for Gather {
+    type Item = Result<Bytes>;
+
+    fn poll_next(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        let this = self.get_mut();
+        loop {
+            let (_, msg) = ready!(this.engine.poll_recv(cx));
+            if let Some(part) = msg.into_single() {
+                return Poll::Ready(Some(Ok(part)));
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Gather, Scatter};
+    use crate::errors::{ConnectionError, Error, SocketError};
+    use crate::sockets::Pull;
+    use crate::Multipart;
+    use bytes::Bytes;
+
+    #[tokio::test]
+    async fn round_robin() {
+        let (first, second) = (Gather::new(), Gather::new());
+        let scatter = Scatter::new();
+        for gather in [&first, &second] {
+            let addr = gather.bind("127.0.0.1", 0).await.unwrap();
+            scatter.connect("127.0.0.1", addr.port()).await.unwrap();
+        }
+
+        for msg in ["a", "b", "c"] {
+            scatter.send(Bytes::from(msg)).await.unwrap();
+        }
+        assert_eq!(first.recv().await.unwrap(), "a");
+        assert_eq!(second.recv().await.unwrap(), "b");
+        assert_eq!(first.recv().await.unwrap(), "c");
+    }
+
+    #[tokio::test]
+    async fn single_part() {
+        let gather = Gather::new();
+        let addr = gather.bind("127.0.0.1", 0).await.unwrap();
+        let scatter = Scatter::new();
+        scatter.connect("127.0.0.1", addr.port()).await.unwrap();
+
+        let multipart = Multipart(vec![Bytes::from("a"), Bytes::from("b")]);
+        assert!(matches!(
+            scatter.send(multipart).await,
+            Err(Error::Socket(SocketError::MultipartNotAllowed(_)))
+        ));
+        scatter.send(Bytes::from("c")).await.unwrap();
+        assert_eq!(gather.recv().await.unwrap(), "c");
+    }
+
+    #[tokio::test]
+    async fn socket_type_mismatch() {
+        let pull = Pull::new();
+        let addr = pull.bind("127.0.0.1", 0).await.unwrap();
+        let scatter = Scatter::new();
+        assert!(matches!(
+            scatter.connect("127.0.0.1", addr.port()).await,
+            Err(Error::Connection(ConnectionError::SocketTypeMismatch(_)))
+        ));
+    }
+}
@@ -0,0 +1,130 @@
+//! Socket types, as announced by the `Socket-Type` property of the READY command.
+use serde::{Deserialize, Serialize};
+use std::fmt;
+
+/// The ZMQ socket types a peer may announce.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Deserialize, Serialize)]
+#[serde(rename_all = "UPPERCASE")]
+pub enum SocketType {
+    Req,
+    Rep,
+    Dealer,
+    Router,
+    Pub,
+    XPub,
+    Sub,
+    XSub,
+    Push,
+    Pull,
+    Pair,
+    Client,
+    Server,
+    Radio,
+    Dish,
+    Scatter,
+    Gather,
+    Channel,
+    Peer,
+}
+
+impl SocketType {
+    const ALL: [Self; 19] = [
+        Self::Req,
+        Self::Rep,
+        Self::Dealer,
+        Self::Router,
+        Self::Pub,
+        Self::XPub,
+        Self::Sub,
+        Self::XSub,
+        Self::Push,
+        Self::Pull,
+        Self::Pair,
+        Self::Client,
+        Self::Server,
+        Self::Radio,
+        Self::Dish,
+        Self::Scatter,
+        Self::Gather,
+        Self::Channel,
+        Self::Peer,
+    ];
+
+    /// The name of the socket type in the READY command.
+    pub fn name(&self) -> &'static str {
+        match self {
+            Self::Req => "REQ",
+            Self::Rep => "REP",
+            Self::Dealer => "DEALER",
+            Self::Router => "ROUTER",
+            Self::Pub => "PUB",
+            Self::XPub => "XPUB",
+            Self::Sub => "SUB",
+            Self::XSub => "XSUB",
+            Self::Push => "PUSH",
+            Self::Pull => "PULL",
+            Self::Pair => "PAIR",
+            Self::Client => "CLIENT",
+            Self::Server => "SERVER",
+            Self::Radio => "RADIO",
+            Self::Dish => "DISH",
+            Self::Scatter => "SCATTER",
+            Self::Gather => "GATHER",
+            Self::Channel => "CHANNEL",
+            Self::Peer => "PEER",
+        }
+    }
+
+    /// The socket type named `name` in a READY command.
+    pub fn from_name(name: &[u8]) -> Option<Self> {
+        Self::ALL
+            .into_iter()
+            .find(|socket_type| socket_type.name().as_bytes() == name)
+    }
+
+    /// Whether a socket of this type can talk with a `peer` socket.
+    pub fn is_compatible(&self, peer: Self) -> bool {
+        use SocketType::*;
+        let peers: &[Self] = match self {
+            Req => &[Rep, Router],
+            Rep => &[Req, Dealer],
+            Dealer => &[Rep, Dealer, Router],
+            Router => &[Req, Dealer, Router],
+            Pub | XPub => &[Sub, XSub],
+            Sub | XSub => &[Pub, XPub],
+            Push => &[Pull],
+            Pull => &[Push],
+            Pair => &[Pair],
+            Client => &[Server],
+            Server => &[Client],
+            Radio => &[Dish],
+            Dish => &[Radio],
+            Scatter => &[Gather],
+            Gather => &[Scatter],
+            Channel => &[Channel],
+            Peer => &[Peer],
+        };
+        peers.contains(&peer)
+    }
+}
+
+impl fmt::Display for SocketType {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.name())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::SocketType;
+
+    #[test]
+    fn symmetric_compatibility() {
+        for local in SocketType::ALL {
+            assert_eq!(SocketType::from_name(local.name().as_bytes()), Some(local));
+            for peer in SocketType::ALL {
+                assert_eq!(local.is_compatible(peer), peer.is_compatible(local));
+            }
+        }
+    }
+}
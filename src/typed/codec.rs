@@ -0,0 +1,250 @@
+//! The encodings of the messages of typed sockets.
+use crate::errors::{ParseError, SocketError};
+
+use serde::de::{DeserializeOwned, DeserializeSeed};
+use serde::Serialize;
+use std::fmt::Display;
+use std::marker::PhantomData;
+
+/// An encoding of values into message bodies.
+pub trait Codec {
+    /// Encode `value` into a message body.
+    fn encode<T: Serialize + ?Sized>(&self, value: &T) -> Result<Vec<u8>, SocketError>;
+
+    /// Decode a value from a message body.
+    fn decode<T: DeserializeOwned>(&self, body: &[u8]) -> Result<T, ParseError> {
+        self.decode_seed(body, PhantomData)
+    }
+
+    /// Decode the value `seed` deserializes from a message body, which must hold nothing else.
+    fn decode_seed<'de, S: DeserializeSeed<'de>>(
+        &self,
+        body: &'de [u8],
+        seed: S,
+    ) -> Result<S::Value, ParseError>;
+}
+
+fn invalid<E: Display>(err: E) -> ParseError {
+    ParseError::InvalidBody(err.to_string())
+}
+
+#[cfg(any(feature = "msgpack", feature = "cbor"))]
+fn trailing<T>(len: usize) -> Result<T, ParseError> {
+    Err(ParseError::InvalidBody(format!(
+        "{len} bytes after the value"
+    )))
+}
+
+/// JSON, with [`serde_json`].
+#[derive(Debug, Clone, Copy, Default)]
+pub struct Json;
+
+impl Codec for Json {
+    fn encode<T: Serialize + ?Sized>(&self, value: &T) -> Result<Vec<u8>, SocketError> {
+        serde_json::to_vec(value).map_err(|err| SocketError::InvalidBody(err.to_string()))
+    }
+
+    fn decode_seed<'de, S: DeserializeSeed<'de>>(
+        &self,
+        body: &'de [u8],
+        seed: S,
+    ) -> Result<S::Value, ParseError> {
+        let mut de = serde_json::Deserializer::from_slice(body);
+        let value = seed.deserialize(&mut de).map_err(invalid)?;
+        de.end().map_err(invalid)?;
+        Ok(value)
+    }
+}
+
+/// MessagePack, with [`rmp_serde`]. Structs are encoded as arrays.
+#[cfg(feature = "msgpack")]
+#[derive(Debug, Clone, Copy, Default)]
+pub struct MsgPack;
+
+#[cfg(feature = "msgpack")]
+impl Codec for MsgPack {
+    fn encode<T: Serialize + ?Sized>(&self, value: &T) -> Result<Vec<u8>, SocketError> {
+        rmp_serde::to_vec(value).map_err(|err| SocketError::InvalidBody(err.to_string()))
+    }
+
+    fn decode_seed<'de, S: DeserializeSeed<'de>>(
+        &self,
+        body: &'de [u8],
+        seed: S,
+    ) -> Result<S::Value, ParseError> {
+        let mut rest = body;
+        let value = seed
+            .deserialize(&mut rmp_serde::Deserializer::new(&mut rest))
+            .map_err(invalid)?;
+        match rest.len() {
+            0 => Ok(value),
+            len => trailing(len),
+        }
+    }
+}
+
+/// CBOR, with [`ciborium`]. Structs are encoded as maps.
+#[cfg(feature = "cbor")]
+#[derive(Debug, Clone, Copy, Default)]
+pub struct Cbor;
+
+#[cfg(feature = "cbor")]
+impl Cbor {
+    /// Decode the only data item of `body`.
+    fn item<T: DeserializeOwned>(body: &[u8]) -> Result<T, ParseError> {
+        let mut rest = body;
+        let item = ciborium::from_reader(&mut rest).map_err(invalid)?;
+        match rest.len() {
+            0 => Ok(item),
+            len => trailing(len),
+        }
+    }
+}
+
+#[cfg(feature = "cbor")]
+impl Codec for Cbor {
+    fn encode<T: Serialize + ?Sized>(&self, value: &T) -> Result<Vec<u8>, SocketError> {
+        let mut buf = Vec::new();
+        ciborium::into_writer(value, &mut buf)
+            .map_err(|err| SocketError::InvalidBody(err.to_string()))?;
+        Ok(buf)
+    }
+
+    fn decode<T: DeserializeOwned>(&self, body: &[u8]) -> Result<T, ParseError> {
+        Self::item(body)
+    }
+
+    fn decode_seed<'de, S: DeserializeSeed<'de>>(
+        &self,
+        body: &'de [u8],
+        seed: S,
+    ) -> Result<S::Value, ParseError> {
+        // ciborium only deserializes owned values, so go through its `Value`
+        seed.deserialize(cbor::Item(Self::item(body)?))
+            .map_err(invalid)
+    }
+}
+
+/// The default encoding of [`bincode`], not self-describing: both peers must agree on the
+/// types of the messages.
+#[cfg(feature = "bincode")]
+#[derive(Debug, Clone, Copy, Default)]
+pub struct Bincode;
+
+#[cfg(feature = "bincode")]
+impl Codec for Bincode {
+    fn encode<T: Serialize + ?Sized>(&self, value: &T) -> Result<Vec<u8>, SocketError> {
+        bincode::serialize(value).map_err(|err| SocketError::InvalidBody(err.to_string()))
+    }
+
+    fn decode_seed<'de, S: DeserializeSeed<'de>>(
+        &self,
+        body: &'de [u8],
+        seed: S,
+    ) -> Result<S::Value, ParseError> {
+        use bincode::Options;
+
+        // the options of `bincode::deserialize`, rejecting trailing bytes
+        bincode::options()
+            .with_fixint_encoding()
+            .deserialize_seed(seed, body)
+            .map_err(invalid)
+    }
+}
+
+/// A deserializer of decoded CBOR data items.
+#[cfg(feature = "cbor")]
+mod cbor {
+    use ciborium::Value;
+    use serde::de::value::{Error, MapAccessDeserializer, MapDeserializer, SeqDeserializer};
+    use serde::de::{self, IntoDeserializer, Visitor};
+
+    /// A data item, deserialized the way ciborium deserializes its encoding.
+    pub struct Item(pub Value);
+
+    impl<'de> IntoDeserializer<'de, Error> for Item {
+        type Deserializer = Self;
+
+        fn into_deserializer(self) -> Self {
+            self
+        }
+    }
+
+    impl<'de> de::Deserializer<'de> for Item {
+        type Error = Error;
+
+        fn deserialize_any<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Error> {
+            match self.0 {
+                Value::Integer(int) => {
+                    let int = i128::from(int);
+                    match (u64::try_from(int), i64::try_from(int)) {
+                        (Ok(int), _) => visitor.visit_u64(int),
+                        (_, Ok(int)) => visitor.visit_i64(int),
+                        _ => visitor.visit_i128(int),
+                    }
+                }
+                Value::Bytes(bytes) => visitor.visit_byte_buf(bytes),
+                Value::Float(float) => visitor.visit_f64(float),
+                Value::Text(text) => visitor.visit_string(text),
+                Value::Bool(bool) => visitor.visit_bool(bool),
+                Value::Null => visitor.visit_unit(),
+                Value::Tag(_, item) => Item(*item).deserialize_any(visitor),
+                Value::Array(items) => {
+                    let mut seq = SeqDeserializer::new(items.into_iter().map(Item));
+                    let value = visitor.visit_seq(&mut seq)?;
+                    seq.end()?;
+                    Ok(value)
+                }
+                Value::Map(entries) => {
+                    let entries = entries.into_iter().map(|(k, v)| (Item(k), Item(v)));
+                    let mut map = MapDeserializer::new(entries);
+                    let value = visitor.visit_map(&mut map)?;
+                    map.end()?;
+                    Ok(value)
+                }
+                _ => Err(de::Error::custom("unsupported CBOR data item")),
+            }
+        }
+
+        fn deserialize_option<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Error> {
+            match self.0 {
+                Value::Null => visitor.visit_none(),
+                _ => visitor.visit_some(self),
+            }
+        }
+
+        fn deserialize_enum<V: Visitor<'de>>(
+            self,
+            _name: &'static str,
+            _variants: &'static [&'static str],
+            visitor: V,
+        ) -> Result<V::Value, Error> {
+            // unit variants are encoded as their name, the others as a map from their name
+            match self.0 {
+                Value::Text(name) => visitor.visit_enum(name.into_deserializer()),
+                Value::Map(entries) if entries.len() == 1 => {
+                    let entries = entries.into_iter().map(|(k, v)| (Item(k), Item(v)));
+                    visitor.visit_enum(MapAccessDeserializer::new(MapDeserializer::new(entries)))
+                }
+                _ => Err(de::Error::custom("expected an enum")),
+            }
+        }
+
+        fn deserialize_newtype_struct<V: Visitor<'de>>(
+            self,
+            _name: &'static str,
+            visitor: V,
+        ) -> Result<V::Value, Error> {
+            visitor.visit_newtype_struct(self)
+        }
+
+        fn is_human_readable(&self) -> bool {
+            false
+        }
+
+        serde::forward_to_deserialize_any! {
+            bool i8 i16 i32 i64 i128 u8 u16 u32 u64 u128 f32 f64 char str string bytes byte_buf
+            unit unit_struct seq tuple tuple_struct map struct identifier ignored_any
+        }
+    }
+}
@@ -0,0 +1,375 @@
+//! The mapping of the fields of structs and tuples to the frames of a multipart message.
+use super::Codec;
+use crate::errors::{ParseError, SocketError};
+
+use bytes::Bytes;
+use serde::de::{self, DeserializeOwned, DeserializeSeed, IntoDeserializer, Visitor};
+use serde::ser::{self, Impossible, Serialize};
+use std::fmt::{self, Display};
+
+/// Encode each field of `value` with the `codec`, in a frame of its own.
+///
+/// Skipped fields, with `#[serde(skip_serializing_if)]`, are sent as empty frames, so fields
+/// encoding to nothing, like a `()` with bincode, can't be split.
+pub fn split<C: Codec, T: Serialize + ?Sized>(
+    codec: &C,
+    value: &T,
+) -> Result<Vec<Bytes>, SocketError> {
+    value
+        .serialize(Fields(codec))
+        .map_err(|Unsplittable(err)| SocketError::InvalidBody(err))
+}
+
+/// Decode a value of type `T` from the frames of its fields.
+///
+/// Each frame is decoded on its own, as the field it stands for.
+pub fn join<C: Codec, T: DeserializeOwned>(codec: &C, frames: &[Bytes]) -> Result<T, ParseError> {
+    T::deserialize(FrameFields { codec, frames }).map_err(|Unjoinable(err)| err)
+}
+
+/// The reason a value can't be decoded from frames.
+#[derive(Debug)]
+struct Unjoinable(ParseError);
+
+impl Display for Unjoinable {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        self.0.fmt(f)
+    }
+}
+
+impl std::error::Error for Unjoinable {}
+
+impl de::Error for Unjoinable {
+    fn custom<E: Display>(msg: E) -> Self {
+        Unjoinable(ParseError::InvalidBody(msg.to_string()))
+    }
+}
+
+/// A deserializer decoding each field of a struct or a tuple from a frame of its own.
+struct FrameFields<'a, 'de, C> {
+    codec: &'a C,
+    frames: &'de [Bytes],
+}
+
+impl<C> FrameFields<'_, '_, C> {
+    fn expect(&self, len: usize) -> Result<(), Unjoinable> {
+        match self.frames.len() {
+            got if got == len => Ok(()),
+            got => Err(Unjoinable(ParseError::UnexpectedFrameCount(len, got))),
+        }
+    }
+}
+
+impl<'de, C: Codec> de::Deserializer<'de> for FrameFields<'_, 'de, C> {
+    type Error = Unjoinable;
+
+    fn deserialize_any<V: Visitor<'de>>(self, _: V) -> Result<V::Value, Unjoinable> {
+        Err(de::Error::custom("only structs and tuples are split"))
+    }
+
+    fn deserialize_struct<V: Visitor<'de>>(
+        self,
+        _name: &'static str,
+        fields: &'static [&'static str],
+        visitor: V,
+    ) -> Result<V::Value, Unjoinable> {
+        self.expect(fields.len())?;
+        // skipped fields are left out, for serde to fill them in with their default
+        let fields = fields
+            .iter()
+            .zip(self.frames)
+            .filter(|(_, frame)| !frame.is_empty());
+        visitor.visit_map(FieldFrames {
+            codec: self.codec,
+            fields,
+            frame: None,
+        })
+    }
+
+    fn deserialize_tuple<V: Visitor<'de>>(
+        self,
+        len: usize,
+        visitor: V,
+    ) -> Result<V::Value, Unjoinable> {
+        self.expect(len)?;
+        visitor.visit_seq(ElementFrames {
+            codec: self.codec,
+            frames: self.frames.iter(),
+        })
+    }
+
+    fn deserialize_tuple_struct<V: Visitor<'de>>(
+        self,
+        _name: &'static str,
+        len: usize,
+        visitor: V,
+    ) -> Result<V::Value, Unjoinable> {
+        self.deserialize_tuple(len, visitor)
+    }
+
+    serde::forward_to_deserialize_any! {
+        bool i8 i16 i32 i64 i128 u8 u16 u32 u64 u128 f32 f64 char str string bytes byte_buf
+        option unit unit_struct newtype_struct seq map enum identifier ignored_any
+    }
+}
+
+/// The fields of a struct, with their frames.
+struct FieldFrames<'a, 'de, C, I> {
+    codec: &'a C,
+    fields: I,
+    frame: Option<&'de Bytes>,
+}
+
+impl<'de, C, I> de::MapAccess<'de> for FieldFrames<'_, 'de, C, I>
+where
+    C: Codec,
+    I: Iterator<Item = (&'static &'static str, &'de Bytes)>,
+{
+    type Error = Unjoinable;
+
+    fn next_key_seed<K: DeserializeSeed<'de>>(
+        &mut self,
+        seed: K,
+    ) -> Result<Option<K::Value>, Unjoinable> {
+        let Some((name, frame)) = self.fields.next() else {
+            return Ok(None);
+        };
+        self.frame = Some(frame);
+        seed.deserialize(name.into_deserializer()).map(Some)
+    }
+
+    fn next_value_seed<S: DeserializeSeed<'de>>(
+        &mut self,
+        seed: S,
+    ) -> Result<S::Value, Unjoinable> {
+        let frame = self.frame.take().expect("value before its key");
+        self.codec.decode_seed(frame, seed).map_err(Unjoinable)
+    }
+}
+
+/// The elements of a tuple, in their frames.
+struct ElementFrames<'a, 'de, C> {
+    codec: &'a C,
+    frames: std::slice::Iter<'de, Bytes>,
+}
+
+impl<'de, C: Codec> de::SeqAccess<'de> for ElementFrames<'_, 'de, C> {
+    type Error = Unjoinable;
+
+    fn next_element_seed<S: DeserializeSeed<'de>>(
+        &mut self,
+        seed: S,
+    ) -> Result<Option<S::Value>, Unjoinable> {
+        self.frames
+            .next()
+            .map(|frame| self.codec.decode_seed(frame, seed).map_err(Unjoinable))
+            .transpose()
+    }
+
+    fn size_hint(&self) -> Option<usize> {
+        Some(self.frames.len())
+    }
+}
+
+/// The reason a value can't be split into frames.
+#[derive(Debug)]
+struct Unsplittable(String);
+
+impl Display for Unsplittable {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.0)
+    }
+}
+
+impl std::error::Error for Unsplittable {}
+
+impl ser::Error for Unsplittable {
+    fn custom<E: Display>(msg: E) -> Self {
+        Unsplittable(msg.to_string())
+    }
+}
+
+fn unsplittable<T>() -> Result<T, Unsplittable> {
+    Err(Unsplittable("only structs and tuples are split".into()))
+}
+
+/// A serializer encoding the fields of a struct or a tuple in frames.
+struct Fields<'a, C>(&'a C);
+
+/// The frames of the fields serialized so far.
+struct Frames<'a, C> {
+    codec: &'a C,
+    frames: Vec<Bytes>,
+}
+
+impl<C: Codec> Frames<'_, C> {
+    fn push<T: Serialize + ?Sized>(&mut self, value: &T) -> Result<(), Unsplittable> {
+        let frame = self.codec.encode(value).map_err(ser::Error::custom)?;
+        self.frames.push(frame.into());
+        Ok(())
+    }
+}
+
+macro_rules! unsplittable {
+    ($($method:ident($($ty:ty),*)),* $(,)?) => {
+        $(
+            fn $method(self, $(_: $ty),*) -> Result<Self::Ok, Unsplittable> {
+                unsplittable()
+            }
+        )*
+    };
+}
+
+impl<'a, C: Codec> ser::Serializer for Fields<'a, C> {
+    type Ok = Vec<Bytes>;
+    type Error = Unsplittable;
+    type SerializeSeq = Impossible<Vec<Bytes>, Unsplittable>;
+    type SerializeTuple = Frames<'a, C>;
+    type SerializeTupleStruct = Frames<'a, C>;
+    type SerializeTupleVariant = Impossible<Vec<Bytes>, Unsplittable>;
+    type SerializeMap = Impossible<Vec<Bytes>, Unsplittable>;
+    type SerializeStruct = Frames<'a, C>;
+    type SerializeStructVariant = Impossible<Vec<Bytes>, Unsplittable>;
+
+    unsplittable! {
+        serialize_bool(bool),
+        serialize_i8(i8),
+        serialize_i16(i16),
+        serialize_i32(i32),
+        serialize_i64(i64),
+        serialize_u8(u8),
+        serialize_u16(u16),
+        serialize_u32(u32),
+        serialize_u64(u64),
+        serialize_f32(f32),
+        serialize_f64(f64),
+        serialize_char(char),
+        serialize_str(&str),
+        serialize_bytes(&[u8]),
+        serialize_none(),
+        serialize_unit(),
+        serialize_unit_struct(&'static str),
+        serialize_unit_variant(&'static str, u32, &'static str),
+    }
+
+    fn serialize_some<T: Serialize + ?Sized>(self, _: &T) -> Result<Vec<Bytes>, Unsplittable> {
+        unsplittable()
+    }
+
+    fn serialize_newtype_struct<T: Serialize + ?Sized>(
+        self,
+        _: &'static str,
+        _: &T,
+    ) -> Result<Vec<Bytes>, Unsplittable> {
+        unsplittable()
+    }
+
+    fn serialize_newtype_variant<T: Serialize + ?Sized>(
+        self,
+        _: &'static str,
+        _: u32,
+        _: &'static str,
+        _: &T,
+    ) -> Result<Vec<Bytes>, Unsplittable> {
+        unsplittable()
+    }
+
+    fn serialize_seq(self, _: Option<usize>) -> Result<Self::SerializeSeq, Unsplittable> {
+        unsplittable()
+    }
+
+    fn serialize_tuple(self, len: usize) -> Result<Frames<'a, C>, Unsplittable> {
+        Ok(Frames {
+            codec: self.0,
+            frames: Vec::with_capacity(len),
+        })
+    }
+
+    fn serialize_tuple_struct(
+        self,
+        _name: &'static str,
+        len: usize,
+    ) -> Result<Frames<'a, C>, Unsplittable> {
+        self.serialize_tuple(len)
+    }
+
+    fn serialize_tuple_variant(
+        self,
+        _: &'static str,
+        _: u32,
+        _: &'static str,
+        _: usize,
+    ) -> Result<Self::SerializeTupleVariant, Unsplittable> {
+        unsplittable()
+    }
+
+    fn serialize_map(self, _: Option<usize>) -> Result<Self::SerializeMap, Unsplittable> {
+        unsplittable()
+    }
+
+    fn serialize_struct(
+        self,
+        _name: &'static str,
+        len: usize,
+    ) -> Result<Frames<'a, C>, Unsplittable> {
+        self.serialize_tuple(len)
+    }
+
+    fn serialize_struct_variant(
+        self,
+        _: &'static str,
+        _: u32,
+        _: &'static str,
+        _: usize,
+    ) -> Result<Self::SerializeStructVariant, Unsplittable> {
+        unsplittable()
+    }
+}
+
+impl<C: Codec> ser::SerializeTuple for Frames<'_, C> {
+    type Ok = Vec<Bytes>;
+    type Error = Unsplittable;
+
+    fn serialize_element<T: Serialize + ?Sized>(&mut self, value: &T) -> Result<(), Unsplittable> {
+        self.push(value)
+    }
+
+    fn end(self) -> Result<Vec<Bytes>, Unsplittable> {
+        Ok(self.frames)
+    }
+}
+
+impl<C: Codec> ser::SerializeTupleStruct for Frames<'_, C> {
+    type Ok = Vec<Bytes>;
+    type Error = Unsplittable;
+
+    fn serialize_field<T: Serialize + ?Sized>(&mut self, value: &T) -> Result<(), Unsplittable> {
+        self.push(value)
+    }
+
+    fn end(self) -> Result<Vec<Bytes>, Unsplittable> {
+        Ok(self.frames)
+    }
+}
+
+impl<C: Codec> ser::SerializeStruct for Frames<'_, C> {
+    type Ok = Vec<Bytes>;
+    type Error = Unsplittable;
+
+    fn serialize_field<T: Serialize + ?Sized>(
+        &mut self,
+        _key: &'static str,
+        value: &T,
+    ) -> Result<(), Unsplittable> {
+        self.push(value)
+    }
+
+    fn skip_field(&mut self, _key: &'static str) -> Result<(), Unsplittable> {
+        self.frames.push(Bytes::new());
+        Ok(())
+    }
+
+    fn end(self) -> Result<Vec<Bytes>, Unsplittable> {
+        Ok(self.frames)
+    }
+}
@@ -0,0 +1,340 @@
+//! Sockets sending and receiving typed values rather than raw frames.
+//!
+//! The values are encoded with a [`Codec`]: JSON is always available, MessagePack, CBOR and
+//! bincode with the `msgpack`, `cbor` and `bincode` features.
+//!
+//! # Exemple
+//!
+//! ```rust,no_run
+//! use futures::{SinkExt, StreamExt};
+//! use serde::{Deserialize, Serialize};
+//! use zmtp::sockets::{Pull, Push};
+//! use zmtp::typed::TypedSocket;
+//!
+//! #[derive(Debug, Serialize, Deserialize)]
+//! struct Job {
+//!     id: u32,
+//!     command: String,
+//! }
+//!
+//! # async fn run() -> zmtp::Result<()> {
+//! let push = Push::new();
+//! push.connect("localhost", 55555).await?;
+//! let mut jobs = TypedSocket::<_, Job>::new(push).with_field_frames();
+//! jobs.send(Job { id: 1, command: "ls".into() }).await?;
+//!
+//! let pull = Pull::new();
+//! pull.bind("localhost", 55556).await?;
+//! let mut jobs = TypedSocket::<_, Job>::new(pull).with_field_frames();
+//! let job: Job = jobs.next().await.unwrap()?;
+//! # Ok(())
+//! # }
+//! ```
+mod codec;
+mod frames;
+#[cfg(feature = "bincode")]
+pub use self::codec::Bincode;
+#[cfg(feature = "cbor")]
+pub use self::codec::Cbor;
+#[cfg(feature = "msgpack")]
+pub use self::codec::MsgPack;
+pub use self::codec::{Codec, Json};
+
+use crate::errors::ParseError;
+use crate::{Multipart, Result};
+
+use bytes::Bytes;
+use futures::{ready, Sink, Stream};
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+use std::marker::PhantomData;
+use std::pin::Pin;
+use std::task::{Context, Poll};
+
+/// A socket sending and receiving values of type `T`, encoded with the codec `C`.
+///
+/// Each value is encoded in a single-part message by default. With
+/// [`with_field_frames`](Self::with_field_frames), each field of a struct or a tuple is encoded
+/// in a frame of its own, so peers can route on the first fields without decoding the rest.
+///
+/// The socket sends values when `S` is a [`Sink`] of messages, and receives them when `S` is a
+/// [`Stream`] of messages. Messages which don't decode to a `T` are received as
+/// [`ParseError`]s, and the socket goes on with the next message.
+pub struct TypedSocket<S, T, C = Json> {
+    socket: S,
+    codec: C,
+    field_frames: bool,
+    value: PhantomData<fn(T) -> T>,
+}
+
+impl<S, T, C: Codec + Default> TypedSocket<S, T, C> {
+    pub fn new(socket: S) -> Self {
+        Self::with_codec(socket, C::default())
+    }
+}
+
+impl<S, T, C: Codec> TypedSocket<S, T, C> {
+    pub fn with_codec(socket: S, codec: C) -> Self {
+        Self {
+            socket,
+            codec,
+            field_frames: false,
+            value: PhantomData,
+        }
+    }
+
+    /// Encode each field of the values in a frame of its own.
+    ///
+    /// `T` must be a struct or a tuple: other values fail to encode.
+    pub fn with_field_frames(mut self) -> Self {
+        self.field_frames = true;
+        self
+    }
+
+    /// Return the wrapped socket.
+    pub fn get_ref(&self) -> &S {
+        &self.socket
+    }
+
+    /// Return the wrapped socket, to send or receive raw messages.
+    pub fn get_mut(&mut self) -> &mut S {
+        &mut self.socket
+    }
+
+    /// Unwrap the socket.
+    pub fn into_inner(self) -> S {
+        self.socket
+    }
+}
+
+impl<S, T: Serialize, C: Codec> TypedSocket<S, T, C> {
+    /// Encode `value` into a message.
+    pub fn encode(&self, value: &T) -> Result<Multipart> {
+        Ok(match self.field_frames {
+            true => frames::split(&self.codec, value)?.into(),
+            false => Bytes::from(self.codec.encode(value)?).into(),
+        })
+    }
+}
+
+impl<S, T: DeserializeOwned, C: Codec> TypedSocket<S, T, C> {
+    /// Decode a value from a message.
+    pub fn decode(&self, msg: Multipart) -> Result<T> {
+        Ok(match self.field_frames {
+            true => frames::join(&self.codec, &msg.0)?,
+            false => {
+                let parts = msg.0.len();
+                let body = msg
+                    .into_single()
+                    .ok_or(ParseError::UnexpectedFrameCount(1, parts))?;
+                self.codec.decode(&body)?
+            }
+        })
+    }
+}
+
+impl<S, T, C> Sink<T> for TypedSocket<S, T, C>
+where
+    S: Sink<Multipart, Error = crate::Error> + Unpin,
+    T: Serialize,
+    C: Codec + Unpin,
+{
+    type Error = crate::Error;
+
+    fn poll_ready(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Result<()>> {
+        Pin::new(&mut self.get_mut().socket).poll_ready(cx)
+    }
+
+    fn start_send(self: Pin<&mut Self>, value: T) -> Result<()> {
+        let this = self.get_mut();
+        let msg = this.encode(&value)?;
+        Pin::new(&mut this.socket).start_send(msg)
+    }
+
+    fn poll_flush(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Result<()>> {
+        Pin::new(&mut self.get_mut().socket).poll_flush(cx)
+    }
+
+    fn poll_close(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Result<()>> {
+        Pin::new(&mut self.get_mut().socket).poll_close(cx)
+    }
+}
+
+impl<S, T, C> Stream for TypedSocket<S, T, C>
+where
+    S: Stream<Item = Result<Multipart>> + Unpin,
+    T: DeserializeOwned,
+    C: Codec + Unpin,
+{
+    type Item = Result<T>;
+
+    fn poll_next(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        let this = self.get_mut();
+        let msg = ready!(Pin::new(&mut this.socket).poll_next(cx));
+        Poll::Ready(msg.map(|msg| this.decode(msg?)))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Codec, Json, TypedSocket};
+    use crate::errors::ParseError;
+    use crate::sockets::{Pull, Push};
+    use crate::{Error, Multipart};
+    use bytes::Bytes;
+    use futures::{SinkExt, StreamExt};
+    use serde::{Deserialize, Serialize};
+
+    #[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+    struct Quote {
+        symbol: String,
+        bid: f64,
+        tags: Vec<String>,
+    }
+
+    #[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+    struct Note {
+        text: String,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        reply_to: Option<u32>,
+    }
+
+    fn quote() -> Quote {
+        Quote {
+            symbol: "ACME".into(),
+            bid: 12.5,
+            tags: vec!["a \"tag\"".into()],
+        }
+    }
+
+    fn round_trip<C: Codec + Default>() {
+        let single = TypedSocket::<(), Quote, C>::new(());
+        let msg = single.encode(&quote()).unwrap();
+        assert_eq!(msg.0.len(), 1);
+        assert_eq!(single.decode(msg).unwrap(), quote());
+
+        let split = TypedSocket::<(), Quote, C>::new(()).with_field_frames();
+        let msg = split.encode(&quote()).unwrap();
+        assert_eq!(msg.0.len(), 3);
+        assert_eq!(split.decode(msg).unwrap(), quote());
+
+        let tuples = TypedSocket::<(), (u8, String), C>::new(()).with_field_frames();
+        let msg = tuples.encode(&(7, "seven".into())).unwrap();
+        assert_eq!(tuples.decode(msg).unwrap(), (7, "seven".into()));
+
+        let notes = TypedSocket::<(), Note, C>::new(()).with_field_frames();
+        for reply_to in [None, Some(3)] {
+            let note = Note {
+                text: "hi".into(),
+                reply_to,
+            };
+            let msg = notes.encode(&note).unwrap();
+            assert_eq!(msg.0.len(), 2);
+            assert_eq!(notes.decode(msg).unwrap(), note);
+        }
+
+        // each frame holds a single field
+        let mut msg = tuples.encode(&(7, "seven".into())).unwrap();
+        let both = [&msg.0[0][..], &msg.0[1][..]].concat();
+        msg.0 = vec![both.into(), Bytes::new()];
+        assert!(matches!(
+            tuples.decode(msg),
+            Err(Error::Parse(ParseError::InvalidBody(_)))
+        ));
+    }
+
+    #[test]
+    fn json() {
+        round_trip::<Json>();
+        let split = TypedSocket::<(), Quote>::new(()).with_field_frames();
+        let msg = split.encode(&quote()).unwrap();
+        assert_eq!(msg.0[0], Bytes::from("\"ACME\""));
+    }
+
+    #[cfg(feature = "msgpack")]
+    #[test]
+    fn msgpack() {
+        round_trip::<super::MsgPack>();
+        let pair = TypedSocket::<(), (u8, u8), super::MsgPack>::new(()).with_field_frames();
+        let packed = Multipart(vec![Bytes::from_static(b"\x01\x02"), Bytes::new()]);
+        assert!(matches!(
+            pair.decode(packed),
+            Err(Error::Parse(ParseError::InvalidBody(_)))
+        ));
+    }
+
+    #[cfg(feature = "cbor")]
+    #[test]
+    fn cbor() {
+        round_trip::<super::Cbor>();
+    }
+
+    #[cfg(feature = "bincode")]
+    #[test]
+    fn bincode() {
+        round_trip::<super::Bincode>();
+    }
+
+    #[test]
+    fn decode_failures() {
+        let split = TypedSocket::<(), Quote>::new(()).with_field_frames();
+        let mut msg = split.encode(&quote()).unwrap();
+        msg.0.pop();
+        assert!(matches!(
+            split.decode(msg),
+            Err(Error::Parse(ParseError::UnexpectedFrameCount(3, 2)))
+        ));
+        let garbage = Multipart(vec!["\"ACME\"".into(), "x".into(), "[]".into()]);
+        assert!(matches!(
+            split.decode(garbage),
+            Err(Error::Parse(ParseError::InvalidBody(_)))
+        ));
+        let injected = Multipart(vec![
+            "\"ACME\",\"bid\":1".into(),
+            "12.5".into(),
+            "[]".into(),
+        ]);
+        assert!(matches!(
+            split.decode(injected),
+            Err(Error::Parse(ParseError::InvalidBody(_)))
+        ));
+        let pair = TypedSocket::<(), (u8, u8)>::new(()).with_field_frames();
+        let empty = Multipart(vec!["1".into(), "".into()]);
+        assert!(matches!(
+            pair.decode(empty),
+            Err(Error::Parse(ParseError::InvalidBody(_)))
+        ));
+        let u32s = TypedSocket::<(), u32>::new(()).with_field_frames();
+        assert!(u32s.encode(&1).is_err());
+        assert!(u32s.decode(Multipart(vec!["1".into()])).is_err());
+
+        let single = TypedSocket::<(), Quote>::new(());
+        let two = Multipart(vec!["{}".into(), "{}".into()]);
+        assert!(matches!(
+            single.decode(two),
+            Err(Error::Parse(ParseError::UnexpectedFrameCount(1, 2)))
+        ));
+    }
+
+    #[tokio::test]
+    async fn typed_pipeline() {
+        let pull = Pull::new();
+        let addr = pull.bind("127.0.0.1", 0).await.unwrap();
+        let push = Push::new();
+        push.connect("127.0.0.1", addr.port()).await.unwrap();
+
+        let mut quotes = TypedSocket::<_, Quote>::new(push).with_field_frames();
+        quotes.send(quote()).await.unwrap();
+        quotes
+            .get_ref()
+            .send(Bytes::from("not json"))
+            .await
+            .unwrap();
+        quotes.send(quote()).await.unwrap();
+
+        let mut quotes = TypedSocket::<_, Quote>::new(pull).with_field_frames();
+        assert_eq!(quotes.next().await.unwrap().unwrap(), quote());
+        assert!(quotes.next().await.unwrap().is_err());
+        assert_eq!(quotes.next().await.unwrap().unwrap(), quote());
+    }
+}